@@ -0,0 +1,143 @@
+//! A small benchmark harness that runs on stable Rust, along with the
+//! synthetic corpus benchmarks search.
+//!
+//! Each benchmark file declares its benchmarks with `benches!`, and is run
+//! with `cargo bench`, optionally followed by a filter on their names. The
+//! output matches that of the unstable `test::Bencher`.
+
+#![allow(dead_code)]
+
+use std::env;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+#[path = "../../src/random.rs"]
+mod random;
+
+use self::random::Random;
+
+// How long each sample of a benchmark runs for, roughly
+const SAMPLE_TIME: Duration = Duration::from_millis(10);
+
+// How many samples of each benchmark are taken
+const SAMPLES: usize = 50;
+
+/// A named benchmark, as declared with `benches!`.
+pub type Bench = (&'static str, fn(&mut Bencher));
+
+/// Times a closure, as `test::Bencher` does.
+pub struct Bencher {
+    iterations: u64,
+    elapsed: Duration,
+}
+
+impl Bencher {
+    /// Runs `f` as many times as this sample calls for.
+    pub fn iter<T, F: FnMut() -> T>(&mut self, mut f: F) {
+        let start = Instant::now();
+
+        for _ in 0..self.iterations {
+            black_box(f());
+        }
+
+        self.elapsed = start.elapsed();
+    }
+}
+
+/// Declares the benchmarks in a file, generating its `main`.
+#[macro_export]
+macro_rules! benches {
+    ($($name:ident),* $(,)*) => {
+        fn main() {
+            harness::run(&[$((stringify!($name), $name as fn(&mut harness::Bencher))),*]);
+        }
+    }
+}
+
+/// Runs the benchmarks whose names contain the filter given on the command
+/// line, or all of them.
+pub fn run(benches: &[Bench]) {
+    // cargo passes --bench, and the filter if there is one
+    let filter = env::args().skip(1).find(|arg| !arg.starts_with("--"));
+
+    let mut benches: Vec<_> = benches.iter().
+        filter(|(name, _)| filter.as_ref().is_none_or(|filter| name.contains(filter.as_str()))).
+        collect();
+
+    benches.sort_by_key(|(name, _)| *name);
+
+    let width = benches.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+
+    for (name, bench) in benches {
+        let (median, spread) = measure(*bench);
+        println!("test {:<width$} ... bench: {:>14.2} ns/iter (+/- {:.2})", name, median, spread, width = width);
+    }
+}
+
+// Times `bench`, returning the median time per iteration over all samples,
+// and the difference between the fastest and slowest sample, in ns
+fn measure(bench: fn(&mut Bencher)) -> (f64, f64) {
+    let mut iterations = 1;
+
+    // calibrate, doubling the iterations until they take long enough
+    loop {
+        let mut bencher = Bencher { iterations, elapsed: Duration::default() };
+        bench(&mut bencher);
+
+        if bencher.elapsed >= SAMPLE_TIME || iterations >= 1 << 40 {
+            break;
+        }
+
+        iterations *= 2;
+    }
+
+    let mut samples: Vec<f64> = (0..SAMPLES).map(|_| {
+        let mut bencher = Bencher { iterations, elapsed: Duration::default() };
+        bench(&mut bencher);
+
+        bencher.elapsed.as_nanos() as f64 / iterations as f64
+    }).collect();
+
+    samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    (samples[SAMPLES / 2], samples[SAMPLES - 1] - samples[0])
+}
+
+const DIRECTORIES: &[&str] = &[
+    "app", "src", "lib", "spec", "test", "models", "views", "controllers",
+    "node_modules", "vendor", "bundle", "components", "helpers", "config",
+    "assets", "images", "Documents", "Music", "café", "Übersicht", "données",
+    "фотографии", "日本語",
+];
+
+const NAMES: &[&str] = &[
+    "order", "order_item", "user", "application", "index", "main", "README",
+    "LineItem", "invoice-template", "naïve_bayes", "résumé", "Ångström",
+    "straße", "отчёт", "写真",
+];
+
+const EXTENSIONS: &[&str] = &[
+    ".rb", ".rs", ".js", ".md", ".html.erb", ".json", ".txt", ".jpg", "",
+];
+
+/// Generates `count` lines, the same every time: mostly paths between one
+/// and eight directories deep, some with non-ASCII names, and every 100th a
+/// long line of several hundred chars.
+pub fn corpus(count: usize) -> Vec<String> {
+    let mut random = Random(0x853c_49e6_748f_ea9b);
+
+    (0..count).map(|i| {
+        let depth = if i % 100 == 99 { 40 } else { 1 + random.below(8) };
+        let mut line = String::new();
+
+        for _ in 0..depth {
+            line.push_str(random.pick(DIRECTORIES));
+            line.push('/');
+        }
+
+        line.push_str(random.pick(NAMES));
+        line.push_str(&random.below(1000).to_string());
+        line.push_str(random.pick(EXTENSIONS));
+        line
+    }).collect()
+}
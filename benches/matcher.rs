@@ -1,37 +1,38 @@
-#![feature(test)]
 extern crate rff;
-extern crate test;
 
-use test::Bencher;
+#[macro_use]
+mod harness;
+
+use harness::Bencher;
 
 use rff::matcher::matches;
+use rff::prefilter::Signature;
+use rff::simd;
 
-#[bench]
 fn bench_matches(b: &mut Bencher) {
     b.iter(|| matches("amor", "app/models/order.rb"))
 }
 
-#[bench]
+fn bench_matches_unicode(b: &mut Bencher) {
+    b.iter(|| matches("ämor", "äpp/models/order.rb"))
+}
+
 fn bench_matches_utf8(b: &mut Bencher) {
     b.iter(|| matches("ß", "WEIẞ"))
 }
 
-#[bench]
 fn bench_matches_mixed(b: &mut Bencher) {
     b.iter(|| matches("abc", "abØ"))
 }
 
-#[bench]
 fn bench_matches_more_specific(b: &mut Bencher) {
     b.iter(|| matches("app/models", "app/models/order.rb"))
 }
 
-#[bench]
 fn bench_matches_mixed_case(b: &mut Bencher) {
     b.iter(|| matches("AMOr", "App/Models/Order.rb"))
 }
 
-#[bench]
 fn bench_matches_multiple(b: &mut Bencher) {
     b.iter(|| {
         matches("amor", "app/models/order.rb");
@@ -43,10 +44,70 @@ fn bench_matches_multiple(b: &mut Bencher) {
     })
 }
 
-#[bench]
 fn bench_matches_eq(b: &mut Bencher) {
     b.iter(|| {
         matches("Gemfile", "Gemfile");
         matches("gemfile", "Gemfile")
     })
 }
+
+// Lines that "models" fails to match, none of them containing a "d"
+fn corpus() -> Vec<String> {
+    (0..1000).map(|i| format!("app/views/layouts/partial_{}.html.erb", i)).collect()
+}
+
+fn bench_matches_corpus(b: &mut Bencher) {
+    let lines = corpus();
+
+    b.iter(|| lines.iter().filter(|line| matches("models", line)).count())
+}
+
+fn bench_matches_corpus_prefiltered(b: &mut Bencher) {
+    let lines = corpus();
+    let signatures: Vec<Signature> = lines.iter().map(|line| Signature::of(line)).collect();
+    let needle = Signature::of("models");
+
+    b.iter(|| {
+        lines.iter().
+            zip(&signatures).
+            filter(|&(line, signature)| signature.may_contain(needle) && matches("models", line)).
+            count()
+    })
+}
+
+const LONG_PATH: &str = "node_modules/@babel/plugin-transform-modules-commonjs/lib/helpers/normalize-and-load-metadata.js";
+
+fn bench_matches_long(b: &mut Bencher) {
+    b.iter(|| matches("nmdata.jsx", LONG_PATH))
+}
+
+fn bench_simd_matches_long(b: &mut Bencher) {
+    b.iter(|| simd::matches("nmdata.jsx", LONG_PATH))
+}
+
+fn bench_simd_matches(b: &mut Bencher) {
+    b.iter(|| simd::matches("amor", "app/models/order.rb"))
+}
+
+fn bench_simd_matches_corpus(b: &mut Bencher) {
+    let lines = corpus();
+
+    b.iter(|| lines.iter().filter(|line| simd::matches("models", line)).count())
+}
+
+benches!(
+    bench_matches,
+    bench_matches_unicode,
+    bench_matches_utf8,
+    bench_matches_mixed,
+    bench_matches_more_specific,
+    bench_matches_mixed_case,
+    bench_matches_multiple,
+    bench_matches_eq,
+    bench_matches_corpus,
+    bench_matches_corpus_prefiltered,
+    bench_matches_long,
+    bench_simd_matches_long,
+    bench_simd_matches,
+    bench_simd_matches_corpus,
+);
@@ -1,17 +1,21 @@
-#![feature(test)]
 extern crate rff;
-extern crate test;
 
-use test::Bencher;
+#[macro_use]
+mod harness;
+
+use harness::Bencher;
 
 use rff::matrix::Matrix;
 
-#[bench]
 fn bench_matrix_new(b: &mut Bencher) {
     b.iter(|| Matrix::new(10, 256))
 }
 
-#[bench]
 fn bench_matrix_new_large_haystack(b: &mut Bencher) {
     b.iter(|| Matrix::new(10, 10_240))
 }
+
+benches!(
+    bench_matrix_new,
+    bench_matrix_new_large_haystack,
+);
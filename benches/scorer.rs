@@ -1,39 +1,38 @@
-#![feature(test)]
 extern crate rff;
-extern crate test;
 
-use test::Bencher;
+#[macro_use]
+mod harness;
+
+use harness::Bencher;
 
 use rff::scorer::{score, score_with_positions, compute_bonus};
 
-#[bench]
 fn bench_score(b: &mut Bencher) {
     b.iter(|| score("amor", "app/models/order.rb"))
 }
 
-#[bench]
+fn bench_score_unicode(b: &mut Bencher) {
+    b.iter(|| score("ämor", "äpp/models/order.rb"))
+}
+
 fn bench_score_empty_needle(b: &mut Bencher) {
     b.iter(|| score("", "app/models/order.rb"))
 }
 
-#[bench]
 fn bench_score_matching(b: &mut Bencher) {
     b.iter(|| score("app/models/order.rb", "app/models/order.rb"))
 }
 
-#[bench]
 fn bench_score_large_haystack(b: &mut Bencher) {
     let large_string = "X".repeat(1024);
     b.iter(|| score("amor", &large_string))
 }
 
-#[bench]
 fn bench_score_huge_haystack(b: &mut Bencher) {
     let huge_string = "X".repeat(1025);
     b.iter(|| score("amor", &huge_string))
 }
 
-#[bench]
 fn bench_score_multiple(b: &mut Bencher) {
     b.iter(|| {
         score("amor", "app/models/order.rb");
@@ -45,12 +44,10 @@ fn bench_score_multiple(b: &mut Bencher) {
     })
 }
 
-#[bench]
 fn bench_score_with_positions(b: &mut Bencher) {
     b.iter(|| score_with_positions("amor", "app/models/order.rb"))
 }
 
-#[bench]
 fn bench_score_multiple_with_positions(b: &mut Bencher) {
     b.iter(|| {
         score_with_positions("amor", "app/models/order.rb");
@@ -62,12 +59,10 @@ fn bench_score_multiple_with_positions(b: &mut Bencher) {
     })
 }
 
-#[bench]
 fn bench_compute_bonus(b: &mut Bencher) {
     b.iter(|| compute_bonus("app/models/this/is/a/strangely/nested/path.rb"))
 }
 
-#[bench]
 fn bench_compute_bonuses(b: &mut Bencher) {
     b.iter(|| {
         compute_bonus("app/models/order.rb");
@@ -78,3 +73,22 @@ fn bench_compute_bonuses(b: &mut Bencher) {
         compute_bonus("vendor/bundle/ruby/gem.rb")
     })
 }
+
+fn bench_compute_bonus_unicode(b: &mut Bencher) {
+    b.iter(|| compute_bonus("äpp/models/this/is/a/strangely/nested/path.rb"))
+}
+
+benches!(
+    bench_score,
+    bench_score_unicode,
+    bench_score_empty_needle,
+    bench_score_matching,
+    bench_score_large_haystack,
+    bench_score_huge_haystack,
+    bench_score_multiple,
+    bench_score_with_positions,
+    bench_score_multiple_with_positions,
+    bench_compute_bonus,
+    bench_compute_bonuses,
+    bench_compute_bonus_unicode,
+);
@@ -0,0 +1,82 @@
+extern crate rff;
+
+#[macro_use]
+mod harness;
+
+use harness::Bencher;
+
+use rff::mode::{Mode, Query};
+use rff::prefilter::Signature;
+use rff::sort;
+use rff::stdin::InputLines;
+
+// How many lines of the synthetic corpus are searched
+const LINES: usize = 20_000;
+
+// Lines of the corpus along with their signatures, as `rff` reads them
+fn input() -> (InputLines, Vec<Signature>) {
+    let lines: InputLines = harness::corpus(LINES).into_iter().collect();
+    let signatures = lines.iter().map(Signature::of).collect();
+
+    (lines, signatures)
+}
+
+// Matches, scores and sorts every line of the input, returning the number
+// of matches
+fn search(mode: Mode, needle: &str, lines: &InputLines, signatures: &[Signature]) -> usize {
+    let query = Query::new(mode, needle, false).unwrap();
+
+    let mut matches: Vec<_> = lines.iter().
+        zip(signatures).
+        filter(|&(line, &signature)| query.may_match(signature) && query.matches(line)).
+        map(|(line, _)| query.score(line)).
+        collect();
+
+    matches.sort_by(|&a, &b| sort::compare_scores(a, b));
+    matches.len()
+}
+
+fn bench_search_fuzzy(b: &mut Bencher) {
+    let (lines, signatures) = input();
+    b.iter(|| search(Mode::Fuzzy, "amor", &lines, &signatures))
+}
+
+fn bench_search_fuzzy_long(b: &mut Bencher) {
+    let (lines, signatures) = input();
+    b.iter(|| search(Mode::Fuzzy, "appmodelsorderrb", &lines, &signatures))
+}
+
+fn bench_search_fuzzy_unicode(b: &mut Bencher) {
+    let (lines, signatures) = input();
+    b.iter(|| search(Mode::Fuzzy, "cafér", &lines, &signatures))
+}
+
+fn bench_search_fuzzy_no_matches(b: &mut Bencher) {
+    let (lines, signatures) = input();
+    b.iter(|| search(Mode::Fuzzy, "zzx", &lines, &signatures))
+}
+
+fn bench_search_exact(b: &mut Bencher) {
+    let (lines, signatures) = input();
+    b.iter(|| search(Mode::Exact, "order", &lines, &signatures))
+}
+
+fn bench_search_regex(b: &mut Bencher) {
+    let (lines, signatures) = input();
+    b.iter(|| search(Mode::Regex, r"models/.*\.rb$", &lines, &signatures))
+}
+
+fn bench_search_typos(b: &mut Bencher) {
+    let (lines, signatures) = input();
+    b.iter(|| search(Mode::Typos, "ordre", &lines, &signatures))
+}
+
+benches!(
+    bench_search_fuzzy,
+    bench_search_fuzzy_long,
+    bench_search_fuzzy_unicode,
+    bench_search_fuzzy_no_matches,
+    bench_search_exact,
+    bench_search_regex,
+    bench_search_typos,
+);
@@ -1,3 +1,4 @@
+use std::env;
 use std::fmt::{Display, Formatter, Result};
 
 pub trait Color {
@@ -6,7 +7,7 @@ pub trait Color {
 }
 
 #[allow(dead_code)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Colors {
     Black,
     Red,
@@ -26,15 +27,38 @@ pub enum Colors {
     LightWhite
 }
 
+// The RGB values of the base colors, as in xterm's default palette
+const BASE_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+    (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+    (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+    (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+];
+
+const BASE_COLORS: [Colors; 16] = [
+    Colors::Black, Colors::Red, Colors::Green, Colors::Yellow,
+    Colors::Blue, Colors::Magenta, Colors::Cyan, Colors::White,
+    Colors::LightBlack, Colors::LightRed, Colors::LightGreen, Colors::LightYellow,
+    Colors::LightBlue, Colors::LightMagenta, Colors::LightCyan, Colors::LightWhite,
+];
+
 impl Color for Colors {
+    // Base colors use the 16-color SGR codes, which every color terminal
+    // understands
     #[inline]
     fn write_fg(&self, f: &mut Formatter) -> Result {
-        write!(f, csi!("38;5;{}m"), *self as u32)
+        match *self as u32 {
+            n if n < 8 => write!(f, csi!("{}m"), 30 + n),
+            n => write!(f, csi!("{}m"), 90 + n - 8)
+        }
     }
 
     #[inline]
     fn write_bg(&self, f: &mut Formatter) -> Result {
-        write!(f, csi!("48;5;{}m"), *self as u32)
+        match *self as u32 {
+            n if n < 8 => write!(f, csi!("{}m"), 40 + n),
+            n => write!(f, csi!("{}m"), 100 + n - 8)
+        }
     }
 }
 
@@ -50,6 +74,145 @@ impl<'a> Color for &'a Colors {
     }
 }
 
+/// A color from the 256-color palette.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AnsiValue(pub u8);
+
+impl AnsiValue {
+    /// The nearest of the 16 base colors.
+    pub fn to_base(self) -> Colors {
+        match self.0 {
+            n if n < 16 => BASE_COLORS[n as usize],
+            _ => self.to_rgb().to_base()
+        }
+    }
+
+    /// The RGB value of this color, as in xterm's default palette.
+    pub fn to_rgb(self) -> Rgb {
+        match self.0 {
+            n if n < 16 => {
+                let (r, g, b) = BASE_RGB[n as usize];
+                Rgb(r, g, b)
+            },
+            n if n < 232 => {
+                let n = n - 16;
+                Rgb(CUBE_LEVELS[(n / 36) as usize], CUBE_LEVELS[(n / 6 % 6) as usize], CUBE_LEVELS[(n % 6) as usize])
+            },
+            n => {
+                let level = 8 + (n - 232) * 10;
+                Rgb(level, level, level)
+            }
+        }
+    }
+}
+
+impl Color for AnsiValue {
+    #[inline]
+    fn write_fg(&self, f: &mut Formatter) -> Result {
+        write!(f, csi!("38;5;{}m"), self.0)
+    }
+
+    #[inline]
+    fn write_bg(&self, f: &mut Formatter) -> Result {
+        write!(f, csi!("48;5;{}m"), self.0)
+    }
+}
+
+// The intensities of each channel in the 6x6x6 color cube of the 256-color
+// palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// A 24-bit truecolor.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// The nearest color in the 256-color palette, from either the color cube
+    /// or the grayscale ramp.
+    pub fn to_ansi_value(self) -> AnsiValue {
+        let Rgb(r, g, b) = self;
+
+        let level = |c: u8| {
+            CUBE_LEVELS.iter().
+                enumerate().
+                min_by_key(|&(_, &l)| (l as i32 - c as i32).abs()).
+                map(|(i, _)| i as u8).
+                unwrap()
+        };
+
+        let cube = AnsiValue(16 + 36 * level(r) + 6 * level(g) + level(b));
+
+        let average = (r as u32 + g as u32 + b as u32) / 3;
+        let gray = AnsiValue(232 + (average.saturating_sub(3) / 10).min(23) as u8);
+
+        if self.distance(gray.to_rgb()) < self.distance(cube.to_rgb()) {
+            gray
+        } else {
+            cube
+        }
+    }
+
+    /// The nearest of the 16 base colors.
+    pub fn to_base(self) -> Colors {
+        BASE_RGB.iter().
+            zip(BASE_COLORS.iter()).
+            min_by_key(|&(&(r, g, b), _)| self.distance(Rgb(r, g, b))).
+            map(|(_, &color)| color).
+            unwrap()
+    }
+
+    fn distance(self, other: Rgb) -> u32 {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2) as u32;
+        d(self.0, other.0) + d(self.1, other.1) + d(self.2, other.2)
+    }
+}
+
+impl Color for Rgb {
+    #[inline]
+    fn write_fg(&self, f: &mut Formatter) -> Result {
+        write!(f, csi!("38;2;{};{};{}m"), self.0, self.1, self.2)
+    }
+
+    #[inline]
+    fn write_bg(&self, f: &mut Formatter) -> Result {
+        write!(f, csi!("48;2;{};{};{}m"), self.0, self.1, self.2)
+    }
+}
+
+/// The range of colors a terminal can display.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub enum Depth {
+    /// The 16 base colors
+    Base,
+    /// The 256-color palette
+    Indexed,
+    /// 24-bit truecolor
+    TrueColor,
+}
+
+impl Depth {
+    /// Detects the terminal's color depth from the `COLORTERM` and `TERM`
+    /// environment variables.
+    pub fn detect() -> Depth {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        Depth::from_env(&colorterm, &term)
+    }
+
+    fn from_env(colorterm: &str, term: &str) -> Depth {
+        let truecolor = colorterm == "truecolor" || colorterm == "24bit" ||
+            term.ends_with("-direct") || term.contains("truecolor") || term.contains("24bit");
+
+        if truecolor {
+            Depth::TrueColor
+        } else if term.contains("256color") {
+            Depth::Indexed
+        } else {
+            Depth::Base
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct Reset;
 
@@ -88,34 +251,74 @@ mod tests {
     use super::*;
 
     macro_rules! test_color {
-        ($test: ident, $name: ident, $value: expr) => {
+        ($test: ident, $name: ident, $fg: expr, $bg: expr) => {
             #[test]
             fn $test() {
                 let fg = format!("{}", Fg(Colors::$name));
                 let bg = format!("{}", Bg(Colors::$name));
 
-                assert_eq!(fg, concat!("\x1b[38;5;", $value, "m"));
-                assert_eq!(bg, concat!("\x1b[48;5;", $value, "m"));
+                assert_eq!(fg, concat!("\x1b[", $fg, "m"));
+                assert_eq!(bg, concat!("\x1b[", $bg, "m"));
             }
         }
     }
 
-    test_color!(black, Black, 0);
-    test_color!(red, Red, 1);
-    test_color!(green, Green, 2);
-    test_color!(yellow, Yellow, 3);
-    test_color!(blue, Blue, 4);
-    test_color!(magenta, Magenta, 5);
-    test_color!(cyan, Cyan, 6);
-    test_color!(white, White, 7);
-    test_color!(light_black, LightBlack, 8);
-    test_color!(light_red, LightRed, 9);
-    test_color!(light_green, LightGreen, 10);
-    test_color!(light_yellow, LightYellow, 11);
-    test_color!(light_blue, LightBlue, 12);
-    test_color!(light_magenta, LightMagenta, 13);
-    test_color!(light_cyan, LightCyan, 14);
-    test_color!(light_white, LightWhite, 15);
+    test_color!(black, Black, 30, 40);
+    test_color!(red, Red, 31, 41);
+    test_color!(green, Green, 32, 42);
+    test_color!(yellow, Yellow, 33, 43);
+    test_color!(blue, Blue, 34, 44);
+    test_color!(magenta, Magenta, 35, 45);
+    test_color!(cyan, Cyan, 36, 46);
+    test_color!(white, White, 37, 47);
+    test_color!(light_black, LightBlack, 90, 100);
+    test_color!(light_red, LightRed, 91, 101);
+    test_color!(light_green, LightGreen, 92, 102);
+    test_color!(light_yellow, LightYellow, 93, 103);
+    test_color!(light_blue, LightBlue, 94, 104);
+    test_color!(light_magenta, LightMagenta, 95, 105);
+    test_color!(light_cyan, LightCyan, 96, 106);
+    test_color!(light_white, LightWhite, 97, 107);
+
+    #[test]
+    fn test_ansi_value() {
+        assert_eq!(format!("{}", Fg(AnsiValue(208))), "\x1b[38;5;208m");
+        assert_eq!(format!("{}", Bg(AnsiValue(208))), "\x1b[48;5;208m");
+    }
+
+    #[test]
+    fn test_rgb() {
+        assert_eq!(format!("{}", Fg(Rgb(255, 135, 0))), "\x1b[38;2;255;135;0m");
+        assert_eq!(format!("{}", Bg(Rgb(255, 135, 0))), "\x1b[48;2;255;135;0m");
+    }
+
+    #[test]
+    fn test_to_ansi_value() {
+        assert_eq!(Rgb(255, 135, 0).to_ansi_value(), AnsiValue(208));
+        assert_eq!(Rgb(0, 0, 0).to_ansi_value(), AnsiValue(16));
+        assert_eq!(Rgb(128, 128, 128).to_ansi_value(), AnsiValue(244));
+        assert_eq!(Rgb(250, 250, 250).to_ansi_value(), AnsiValue(231));
+    }
+
+    #[test]
+    fn test_to_base() {
+        assert_eq!(Rgb(250, 10, 10).to_base(), Colors::LightRed);
+        assert_eq!(Rgb(190, 0, 190).to_base(), Colors::Magenta);
+        assert_eq!(AnsiValue(9).to_base(), Colors::LightRed);
+        assert_eq!(AnsiValue(208).to_base(), Colors::Yellow);
+        assert_eq!(AnsiValue(236).to_base(), Colors::Black);
+    }
+
+    #[test]
+    fn test_depth_from_env() {
+        assert_eq!(Depth::from_env("truecolor", "xterm"), Depth::TrueColor);
+        assert_eq!(Depth::from_env("24bit", ""), Depth::TrueColor);
+        assert_eq!(Depth::from_env("", "xterm-direct"), Depth::TrueColor);
+        assert_eq!(Depth::from_env("", "xterm-256color"), Depth::Indexed);
+        assert_eq!(Depth::from_env("", "screen-256color"), Depth::Indexed);
+        assert_eq!(Depth::from_env("", "xterm"), Depth::Base);
+        assert_eq!(Depth::from_env("", ""), Depth::Base);
+    }
 
     #[test]
     fn test_reset() {
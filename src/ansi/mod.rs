@@ -4,4 +4,6 @@ mod macros;
 pub mod cursor;
 pub mod clear;
 pub mod color;
+pub mod parse;
+pub mod screen;
 pub mod style;
@@ -0,0 +1,208 @@
+//! Parsing of ANSI escape sequences embedded in input lines, such as those
+//! produced by `ls --color` or `rg --color=always`.
+
+/// The SGR (colour and style) sequences in effect from a char onwards.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    /// The index of the first char styled by this span, within the line with
+    /// escape sequences removed
+    pub start: usize,
+    /// The SGR sequences that, written after a reset, recreate the style. An
+    /// empty string means the default style.
+    pub sgr: String,
+}
+
+/// Removes all escape sequences from `line`, returning the remaining text and
+/// the spans of SGR styling applied to it.
+///
+/// # Examples
+///
+/// ```
+/// use rff::ansi::parse::parse;
+///
+/// let (text, spans) = parse("\x1b[34mbin\x1b[0m/rff");
+/// assert_eq!(text, "bin/rff");
+/// assert_eq!(spans[0].sgr, "\x1b[34m");
+/// assert_eq!(spans[1].start, 3);
+/// ```
+pub fn parse(line: &str) -> (String, Vec<Span>) {
+    let mut text = String::with_capacity(line.len());
+    let mut spans: Vec<Span> = vec![];
+
+    let mut sgr = String::new();
+    let mut index = 0;
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((offset, ch)) = chars.next() {
+        if ch != '\x1b' {
+            text.push(ch);
+            index += 1;
+            continue;
+        }
+
+        match chars.peek().map(|&(_, next)| next) {
+            // Control Sequence Introducer, ended by a byte in the range @ to ~
+            Some('[') => {
+                chars.next();
+
+                let mut end = line.len();
+                let mut last = None;
+
+                for (i, c) in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        end = i + c.len_utf8();
+                        last = Some(c);
+                        break;
+                    }
+                }
+
+                if last == Some('m') {
+                    let sequence = &line[offset..end];
+                    let params = &sequence[2..sequence.len() - 1];
+
+                    if params.is_empty() || params == "0" {
+                        sgr.clear();
+                    } else if params.starts_with("0;") {
+                        sgr.clear();
+                        sgr.push_str(sequence);
+                    } else {
+                        sgr.push_str(sequence);
+                    }
+
+                    push_span(&mut spans, index, &sgr);
+                }
+            },
+
+            // Operating System Command, ended by BEL or ST (ESC \)
+            Some(']') => {
+                chars.next();
+
+                while let Some((_, c)) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+
+                    if c == '\x1b' {
+                        if let Some(&(_, '\\')) = chars.peek() {
+                            chars.next();
+                        }
+
+                        break;
+                    }
+                }
+            },
+
+            // any other escape is any number of intermediate bytes, in the
+            // range space to /, followed by a single final byte
+            Some(_) => {
+                for (_, c) in chars.by_ref() {
+                    if !(' '..='/').contains(&c) {
+                        break;
+                    }
+                }
+            },
+
+            None => {}
+        }
+    }
+
+    (text, spans)
+}
+
+/// Removes all escape sequences from `line`.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(rff::ansi::parse::strip("\x1b[01;34mbin\x1b[0m"), "bin");
+/// ```
+pub fn strip(line: &str) -> String {
+    parse(line).0
+}
+
+/// Finds the span styling the char at `index`, if any.
+pub fn span_at(spans: &[Span], index: usize) -> Option<&Span> {
+    spans.iter().rev().find(|span| span.start <= index)
+}
+
+// Records that `sgr` is in effect from `index`, replacing any span that would
+// otherwise style no chars
+fn push_span(spans: &mut Vec<Span>, index: usize, sgr: &str) {
+    if let Some(last) = spans.last_mut() {
+        if last.start == index {
+            last.sgr = sgr.to_string();
+            return;
+        }
+
+        if last.sgr == sgr {
+            return;
+        }
+    } else if sgr.is_empty() {
+        return;
+    }
+
+    spans.push(Span { start: index, sgr: sgr.to_string() });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(start: usize, sgr: &str) -> Span {
+        Span { start, sgr: sgr.to_string() }
+    }
+
+    #[test]
+    fn test_parse_plain() {
+        assert_eq!(parse("app/models/order.rb"), (String::from("app/models/order.rb"), vec![]));
+        assert_eq!(parse(""), (String::new(), vec![]));
+    }
+
+    #[test]
+    fn test_parse_sgr() {
+        let (text, spans) = parse("\x1b[01;34mbin\x1b[0m/\x1b[31mrff\x1b[m");
+        assert_eq!(text, "bin/rff");
+        assert_eq!(spans, vec![
+            span(0, "\x1b[01;34m"),
+            span(3, ""),
+            span(4, "\x1b[31m"),
+            span(7, ""),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_accumulates() {
+        let (text, spans) = parse("\x1b[1m\x1b[31mab\x1b[0;32mc");
+        assert_eq!(text, "abc");
+        assert_eq!(spans, vec![
+            span(0, "\x1b[1m\x1b[31m"),
+            span(2, "\x1b[0;32m"),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_other_sequences() {
+        // erase line, an OSC 8 hyperlink, and a charset designation
+        let line = "a\x1b[Kb\x1b]8;;http://example.com\x07c\x1b]8;;\x1b\\d\x1b(Be";
+        assert_eq!(parse(line), (String::from("abcde"), vec![]));
+
+        // truncated sequences are dropped
+        assert_eq!(strip("ab\x1b[3"), "ab");
+        assert_eq!(strip("ab\x1b"), "ab");
+    }
+
+    #[test]
+    fn test_parse_unicode() {
+        let (text, spans) = parse("山\x1b[31m田\x1b[0m");
+        assert_eq!(text, "山田");
+        assert_eq!(spans, vec![span(1, "\x1b[31m"), span(2, "")]);
+    }
+
+    #[test]
+    fn test_span_at() {
+        let spans = vec![span(2, "\x1b[31m"), span(4, "")];
+        assert_eq!(span_at(&spans, 0), None);
+        assert_eq!(span_at(&spans, 3), Some(&spans[0]));
+        assert_eq!(span_at(&spans, 9), Some(&spans[1]));
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt::{Display, Formatter, Result};
+
+// Switches to the alternate screen buffer, saving the cursor position
+generate_csi_struct!(ToAlternate, "?1049h");
+
+// Switches back to the main screen buffer, restoring the cursor position
+generate_csi_struct!(ToMain, "?1049l");
+
+// Begins a synchronized update, during which the terminal holds off
+// displaying output. Terminals without support ignore it.
+generate_csi_struct!(BeginSync, "?2026h");
+
+// Ends a synchronized update, displaying everything written during it
+generate_csi_struct!(EndSync, "?2026l");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_alternate() {
+        let s = format!("{}", ToAlternate);
+        assert_eq!(s, "\x1b[?1049h");
+    }
+
+    #[test]
+    fn to_main() {
+        let s = format!("{}", ToMain);
+        assert_eq!(s, "\x1b[?1049l");
+    }
+
+    #[test]
+    fn begin_sync() {
+        let s = format!("{}", BeginSync);
+        assert_eq!(s, "\x1b[?2026h");
+    }
+
+    #[test]
+    fn end_sync() {
+        let s = format!("{}", EndSync);
+        assert_eq!(s, "\x1b[?2026l");
+    }
+}
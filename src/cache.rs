@@ -0,0 +1,222 @@
+//! A cache of the matches found for previous search terms.
+//!
+//! When a search term is edited back to one searched before, its matches can
+//! be restored without searching again. When it's extended instead, only the
+//! matches of the longest cached prefix need searching, as long as the term's
+//! `Mode` narrows.
+
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::Arc;
+
+use IndexedMatch;
+use mode::Mode;
+use normalize::normalize;
+
+/// What a set of matches was found for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Key {
+    /// The search term, normalized as it's matched (see `Query`)
+    pub needle: String,
+    pub mode: Mode,
+    /// Whether the matches were scored and sorted
+    pub sorted: bool,
+}
+
+impl Key {
+    /// The key for `search` in `mode`, normalized with diacritics folded away
+    /// if `fold` is set.
+    pub fn new(search: &str, mode: Mode, fold: bool, sorted: bool) -> Key {
+        Key { needle: normalize(search, fold).into_owned(), mode, sorted }
+    }
+
+    /// Whether the matches for this key are a subset of those for `other`,
+    /// as its needle extends `other`'s in a mode that narrows. The needles are
+    /// compared normalized, since typing a combining mark can change the
+    /// character before it rather than append one.
+    pub fn narrows(&self, other: &Key) -> bool {
+        self.mode.narrows() &&
+            self.mode == other.mode &&
+            self.sorted == other.sorted &&
+            self.needle.len() > other.needle.len() &&
+            self.needle.starts_with(&other.needle)
+    }
+}
+
+/// A match whose memory use can be estimated, to bound a `Cache` by.
+pub trait Size {
+    /// Roughly how many bytes the match takes up, including on the heap.
+    fn size(&self) -> usize {
+        mem::size_of_val(self)
+    }
+}
+
+impl<'a> Size for IndexedMatch<'a> {
+    fn size(&self) -> usize {
+        mem::size_of_val(self) + self.3.capacity() * mem::size_of::<usize>()
+    }
+}
+
+/// A cache of match sets by `Key`, bounded by the approximate number of bytes
+/// the matches held take up, which evicts the least recently used sets first.
+#[derive(Debug)]
+pub struct Cache<T> {
+    // least recently used first, each with the size of its matches
+    entries: VecDeque<(Key, Arc<Vec<T>>, usize)>,
+    size: usize,
+    capacity: usize,
+}
+
+impl<T: Size> Cache<T> {
+    /// Creates an empty Cache holding up to roughly `capacity` bytes of
+    /// matches in total.
+    pub fn new(capacity: usize) -> Cache<T> {
+        Cache { entries: VecDeque::new(), size: 0, capacity }
+    }
+
+    /// Finds the matches cached for `key`, marking them as recently used.
+    pub fn get(&mut self, key: &Key) -> Option<Arc<Vec<T>>> {
+        let position = self.entries.iter().position(|entry| entry.0 == *key)?;
+        let entry = self.entries.remove(position)?;
+        let matches = entry.1.clone();
+
+        self.entries.push_back(entry);
+        Some(matches)
+    }
+
+    /// Finds the matches cached for the longest search term that `key`
+    /// narrows (see `Key::narrows`).
+    pub fn longest_prefix(&self, key: &Key) -> Option<Arc<Vec<T>>> {
+        self.entries.iter().
+            filter(|(cached, _, _)| key.narrows(cached)).
+            max_by_key(|(cached, _, _)| cached.needle.len()).
+            map(|entry| entry.1.clone())
+    }
+
+    /// Caches `matches` for `key`, evicting the least recently used matches
+    /// to make room. Sets too large to ever fit aren't cached.
+    pub fn insert(&mut self, key: Key, matches: Arc<Vec<T>>) {
+        let size = matches.iter().map(Size::size).sum::<usize>();
+
+        if size > self.capacity {
+            return;
+        }
+
+        if let Some(position) = self.entries.iter().position(|entry| entry.0 == key) {
+            if let Some((_, _, evicted)) = self.entries.remove(position) {
+                self.size -= evicted;
+            }
+        }
+
+        while self.size + size > self.capacity {
+            match self.entries.pop_front() {
+                Some((_, _, evicted)) => self.size -= evicted,
+                None => break
+            }
+        }
+
+        self.size += size;
+        self.entries.push_back((key, matches, size));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Size for usize {}
+
+    // The capacity for `n` of the matches below
+    fn bytes(n: usize) -> usize {
+        n * mem::size_of::<usize>()
+    }
+
+    fn key(search: &str) -> Key {
+        Key::new(search, Mode::Fuzzy, false, true)
+    }
+
+    fn matches(n: usize) -> Arc<Vec<usize>> {
+        Arc::new((0..n).collect())
+    }
+
+    #[test]
+    fn test_narrows() {
+        assert!(key("abc").narrows(&key("ab")));
+        assert!(key("abc").narrows(&key("")));
+        assert!(!key("abc").narrows(&key("abc")));
+        assert!(!key("abc").narrows(&key("b")));
+        assert!(!key("ab").narrows(&key("abc")));
+        assert!(!Key { sorted: false, ..key("abc") }.narrows(&key("ab")));
+
+        let regex = |search| Key { mode: Mode::Regex, ..key(search) };
+        assert!(!regex("abc").narrows(&regex("ab")));
+
+        // a combining mark composes with the character before it
+        assert!(!key("e\u{301}").narrows(&key("e")));
+        assert!(key("\u{e9}x").narrows(&key("e\u{301}")));
+
+        let folded = |search| Key::new(search, Mode::Fuzzy, true, true);
+        assert!(!folded("e\u{301}").narrows(&folded("e")));
+        assert_eq!(folded("e\u{301}"), folded("e"));
+    }
+
+    #[test]
+    fn test_get() {
+        let mut cache = Cache::new(bytes(10));
+        cache.insert(key("ab"), matches(2));
+
+        assert_eq!(cache.get(&key("ab")), Some(matches(2)));
+        assert_eq!(cache.get(&key("a")), None);
+
+        let unsorted = Key { sorted: false, ..key("ab") };
+        assert_eq!(cache.get(&unsorted), None);
+    }
+
+    #[test]
+    fn test_longest_prefix() {
+        let mut cache = Cache::new(bytes(20));
+        cache.insert(key(""), matches(5));
+        cache.insert(key("a"), matches(3));
+        cache.insert(key("ab"), matches(2));
+        cache.insert(key("b"), matches(1));
+
+        assert_eq!(cache.longest_prefix(&key("abc")), Some(matches(2)));
+        assert_eq!(cache.longest_prefix(&key("ac")), Some(matches(3)));
+        assert_eq!(cache.longest_prefix(&key("c")), Some(matches(5)));
+
+        // an exact match isn't a prefix
+        assert_eq!(cache.longest_prefix(&key("a")), Some(matches(5)));
+
+        let regex = Key { mode: Mode::Regex, ..key("abc") };
+        assert_eq!(cache.longest_prefix(&regex), None);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut cache = Cache::new(bytes(5));
+        cache.insert(key("a"), matches(3));
+        cache.insert(key("b"), matches(2));
+
+        // using "a" makes "b" the least recently used
+        assert!(cache.get(&key("a")).is_some());
+        cache.insert(key("c"), matches(1));
+
+        assert!(cache.get(&key("a")).is_some());
+        assert!(cache.get(&key("b")).is_none());
+        assert!(cache.get(&key("c")).is_some());
+
+        cache.insert(key("d"), matches(6));
+        assert!(cache.get(&key("d")).is_none());
+        assert!(cache.get(&key("a")).is_some());
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut cache = Cache::new(bytes(5));
+        cache.insert(key("a"), matches(3));
+        cache.insert(key("a"), matches(4));
+
+        assert_eq!(cache.get(&key("a")), Some(matches(4)));
+        assert_eq!(cache.size, bytes(4));
+    }
+}
@@ -12,3 +12,4 @@ pub const SCORE_MATCH_SLASH:       f64 = 0.9;
 pub const SCORE_MATCH_WORD:        f64 = 0.8;
 pub const SCORE_MATCH_CAPITAL:     f64 = 0.7;
 pub const SCORE_MATCH_DOT:         f64 = 0.6;
+pub const SCORE_TYPO:              f64 = -1.0;
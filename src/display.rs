@@ -0,0 +1,280 @@
+//! Laying out lines for display in a terminal.
+//!
+//! Lines are split into grapheme clusters, so that combining sequences and
+//! emoji are never broken apart, and measured in terminal columns, so that
+//! double-width (e.g. CJK) text is truncated correctly.
+//!
+//! Lines too long to fit are scrolled horizontally so their matched text stays
+//! in view, with a marker standing in for the text cut off on the left.
+
+use std::borrow::Cow;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// The marker drawn in place of text that doesn't fit.
+pub const ELLIPSIS: &str = "…";
+
+/// The marker drawn in place of text scrolled out of view to the left.
+pub const SCROLLED: &str = "..";
+
+/// A single grapheme cluster of a line, ready to be written out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cell<'a> {
+    /// The text of the cluster, with tabs expanded to spaces
+    pub text: Cow<'a, str>,
+    /// The index of the cluster's first char within the line, or `None` for
+    /// the markers standing in for text that doesn't fit
+    pub index: Option<usize>,
+    /// The number of terminal columns the text occupies
+    pub width: usize,
+    /// Whether the cluster contains any matched chars
+    pub highlighted: bool,
+}
+
+impl<'a> Cell<'a> {
+    fn marker(text: &'static str) -> Cell<'a> {
+        Cell {
+            text: Cow::Borrowed(text),
+            index: None,
+            width: text.width(),
+            highlighted: false,
+        }
+    }
+}
+
+/// Splits `line` into `Cell`s, highlighting those containing any of the
+/// char `positions`.
+///
+/// Tabs are expanded to the next multiple of `tabstop` columns.
+pub fn cells<'a>(line: &'a str, positions: &[usize], tabstop: usize) -> Vec<Cell<'a>> {
+    let tabstop = tabstop.max(1);
+
+    let mut cells = Vec::with_capacity(line.len());
+    let mut column = 0;
+    let mut index = 0;
+
+    for grapheme in line.graphemes(true) {
+        let start = index;
+        let length = grapheme.chars().count();
+        let highlighted = positions.iter().any(|&p| p >= start && p < start + length);
+        index += length;
+
+        let (text, width) = if grapheme == "\t" {
+            let width = tabstop - column % tabstop;
+            (Cow::Owned(" ".repeat(width)), width)
+        } else {
+            (Cow::Borrowed(grapheme), grapheme.width())
+        };
+
+        column += width;
+        cells.push(Cell { text, index: Some(start), width, highlighted });
+    }
+
+    cells
+}
+
+/// Lays out `line` as `cells` does, fitting it within `width` columns.
+///
+/// If the last highlighted cell wouldn't otherwise be visible, the line is
+/// scrolled to bring it into view. Any text that still doesn't fit is
+/// truncated with an ellipsis.
+pub fn layout<'a>(line: &'a str, positions: &[usize], width: usize, tabstop: usize) -> Vec<Cell<'a>> {
+    let cells = cells(line, positions, tabstop);
+    let offset = match_offset(&cells, width);
+    scroll(cells, offset, width)
+}
+
+/// The number of columns `cells` must be scrolled by for the last highlighted
+/// cell to be visible within `width` columns.
+pub fn match_offset(cells: &[Cell], width: usize) -> usize {
+    let mut total = 0;
+    let mut end = 0;
+
+    for cell in cells {
+        total += cell.width;
+
+        if cell.highlighted {
+            end = total;
+        }
+    }
+
+    // a truncated line leaves room for all but the ellipsis
+    if total <= width || end < width {
+        return 0;
+    }
+
+    // leave room for the leading marker, and the trailing ellipsis if the
+    // line continues past the match
+    let trailing = if end < total { ELLIPSIS.width() } else { 0 };
+    (end + SCROLLED.width() + trailing).saturating_sub(width)
+}
+
+/// The largest useful offset for `scroll`, which brings the end of `cells`
+/// up against the right edge of `width` columns.
+pub fn max_offset(cells: &[Cell], width: usize) -> usize {
+    let total = total_width(cells);
+
+    if total <= width {
+        0
+    } else {
+        total - width.saturating_sub(SCROLLED.width())
+    }
+}
+
+/// Scrolls `cells` left by `offset` columns, replacing the text scrolled out
+/// of view with a marker, then truncates them to `width` columns.
+///
+/// A wide cell straddling the left edge is scrolled out of view entirely.
+pub fn scroll(cells: Vec<Cell>, offset: usize, width: usize) -> Vec<Cell> {
+    if offset == 0 || width <= SCROLLED.width() {
+        return truncate(cells, width);
+    }
+
+    let mut column = 0;
+    let mut scrolled = vec![Cell::marker(SCROLLED)];
+
+    scrolled.extend(cells.into_iter().skip_while(|cell| {
+        let start = column;
+        column += cell.width;
+        start < offset
+    }));
+
+    truncate(scrolled, width)
+}
+
+/// Truncates `cells` to fit within `width` columns, replacing the last of
+/// those that fit with an ellipsis if any had to be removed.
+pub fn truncate(mut cells: Vec<Cell>, width: usize) -> Vec<Cell> {
+    if total_width(&cells) <= width {
+        return cells;
+    }
+
+    if width == 0 {
+        cells.clear();
+        return cells;
+    }
+
+    let mut used = 0;
+    let fits = cells.iter().
+        take_while(|cell| {
+            used += cell.width;
+            used < width
+        }).
+        count();
+
+    cells.truncate(fits);
+    cells.push(Cell::marker(ELLIPSIS));
+    cells
+}
+
+/// The total number of columns occupied by `cells`.
+pub fn total_width(cells: &[Cell]) -> usize {
+    cells.iter().map(|cell| cell.width).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(cells: &[Cell]) -> String {
+        cells.iter().map(|cell| cell.text.as_ref()).collect()
+    }
+
+    fn highlighted(cells: &[Cell]) -> String {
+        cells.iter().filter(|cell| cell.highlighted).map(|cell| cell.text.as_ref()).collect()
+    }
+
+    #[test]
+    fn test_cells() {
+        let laid_out = cells("app/models", &[0, 4], 8);
+        assert_eq!(laid_out.len(), 10);
+        assert_eq!(highlighted(&laid_out), "am");
+    }
+
+    #[test]
+    fn test_cells_graphemes() {
+        // a decomposed é is a single cell, highlighted by its base char
+        let laid_out = cells("cafe\u{301}s", &[3], 8);
+        assert_eq!(laid_out.len(), 5);
+        assert_eq!(laid_out[3].text, "e\u{301}");
+        assert_eq!(laid_out[3].width, 1);
+        assert_eq!(laid_out[4].index, Some(5));
+        assert_eq!(highlighted(&laid_out), "e\u{301}");
+
+        // the following char's position is unaffected
+        let laid_out = cells("cafe\u{301}s", &[5], 8);
+        assert_eq!(highlighted(&laid_out), "s");
+    }
+
+    #[test]
+    fn test_cells_wide() {
+        let laid_out = cells("山田/x", &[1, 3], 8);
+        assert_eq!(laid_out.iter().map(|cell| cell.width).collect::<Vec<_>>(), vec![2, 2, 1, 1]);
+        assert_eq!(highlighted(&laid_out), "田x");
+    }
+
+    #[test]
+    fn test_cells_tabs() {
+        assert_eq!(text(&cells("a\tb", &[], 4)), "a   b");
+        assert_eq!(text(&cells("abcd\tb", &[], 4)), "abcd    b");
+        assert_eq!(text(&cells("\t\tb", &[], 2)), "    b");
+    }
+
+    #[test]
+    fn test_layout_truncates() {
+        assert_eq!(text(&layout("abcdef", &[], 6, 8)), "abcdef");
+        assert_eq!(text(&layout("abcdef", &[], 5, 8)), "abcd…");
+        assert_eq!(text(&layout("abcdef", &[], 1, 8)), "…");
+        assert_eq!(text(&layout("abcdef", &[], 0, 8)), "");
+    }
+
+    #[test]
+    fn test_layout_scrolls_to_match() {
+        // matches within view don't scroll
+        assert_eq!(text(&layout("abcdefghij", &[3], 6, 8)), "abcde…");
+
+        assert_eq!(text(&layout("abcdefghij", &[7], 6, 8)), "..fgh…");
+        assert_eq!(text(&layout("abcdefghij", &[9], 6, 8)), "..ghij");
+
+        let cells = layout("abcdefghij", &[1, 8], 6, 8);
+        assert_eq!(text(&cells), "..ghij");
+        assert_eq!(highlighted(&cells), "i");
+    }
+
+    #[test]
+    fn test_scroll() {
+        let line = || cells("abcdefghij", &[], 8);
+
+        assert_eq!(text(&scroll(line(), 0, 6)), "abcde…");
+        assert_eq!(text(&scroll(line(), 2, 6)), "..cde…");
+        assert_eq!(text(&scroll(line(), max_offset(&line(), 6), 6)), "..ghij");
+        assert_eq!(text(&scroll(line(), 20, 6)), "..");
+
+        // too narrow to show the marker
+        assert_eq!(text(&scroll(line(), 2, 2)), "a…");
+    }
+
+    #[test]
+    fn test_scroll_wide() {
+        let line = cells("山田山田山田", &[], 8);
+        assert_eq!(text(&scroll(line, 1, 6)), "..田…");
+    }
+
+    #[test]
+    fn test_max_offset() {
+        assert_eq!(max_offset(&cells("abcdefghij", &[], 8), 6), 6);
+        assert_eq!(max_offset(&cells("abc", &[], 8), 6), 0);
+    }
+
+    #[test]
+    fn test_layout_truncates_wide() {
+        // a wide char that would straddle the edge is dropped entirely
+        let cells = layout("山田山田", &[], 6, 8);
+        assert_eq!(text(&cells), "山田…");
+        assert!(total_width(&cells) <= 6);
+
+        let cells = layout("山田山田", &[], 5, 8);
+        assert_eq!(text(&cells), "山田…");
+    }
+}
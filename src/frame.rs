@@ -0,0 +1,122 @@
+//! A model of what the interface has drawn on screen, so that each redraw
+//! only writes the rows that changed since the last.
+
+use std::cmp;
+use std::io::{self, Write};
+
+use ansi::{clear, cursor, screen};
+
+/// The rows of the interface as drawn, along with where the cursor was left.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// The output written for each row, from the interface's first row down
+    pub rows: Vec<Vec<u8>>,
+    /// The row the cursor is left on, counted from the first row
+    pub cursor_row: usize,
+    /// The 1-based column the cursor is left on
+    pub cursor_column: u16,
+}
+
+impl Frame {
+    /// Writes the output that turns the `previous` frame on screen into this
+    /// one, as a single synchronized update.
+    ///
+    /// The cursor must be where `previous` left it or, without a previous
+    /// frame, on the interface's first row. In that case every row is drawn,
+    /// clearing whatever was on screen before.
+    pub fn draw<W: Write>(&self, previous: Option<&Frame>, out: &mut W) -> io::Result<()> {
+        let mut row = previous.map_or(0, |frame| frame.cursor_row);
+        let previous_rows = previous.map_or(0, |frame| frame.rows.len());
+
+        write!(out, "{}", screen::BeginSync)?;
+
+        if previous.is_none() {
+            write!(out, "{}{}", cursor::Column(1), clear::Screen)?;
+        }
+
+        for i in 0..cmp::max(self.rows.len(), previous_rows) {
+            let new = self.rows.get(i);
+            let old = previous.and_then(|frame| frame.rows.get(i));
+
+            if previous.is_some() && new == old {
+                continue;
+            }
+
+            move_to(out, &mut row, i)?;
+            write!(out, "{}", cursor::Column(1))?;
+
+            match new {
+                Some(text) => {
+                    out.write_all(text)?;
+                    write!(out, "{}", clear::AfterCursor)?;
+                },
+                None => write!(out, "{}", clear::Line)?
+            }
+        }
+
+        move_to(out, &mut row, self.cursor_row)?;
+        write!(out, "{}{}", cursor::Column(self.cursor_column), screen::EndSync)
+    }
+}
+
+// Moves the cursor vertically from `row` to `target`
+fn move_to<W: Write>(out: &mut W, row: &mut usize, target: usize) -> io::Result<()> {
+    if target < *row {
+        write!(out, "{}", cursor::Up((*row - target) as u16))?;
+    } else if target > *row {
+        write!(out, "{}", cursor::Down((target - *row) as u16))?;
+    }
+
+    *row = target;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(rows: &[&str], cursor_row: usize, cursor_column: u16) -> Frame {
+        Frame {
+            rows: rows.iter().map(|row| row.as_bytes().to_vec()).collect(),
+            cursor_row,
+            cursor_column,
+        }
+    }
+
+    fn draw(next: &Frame, previous: Option<&Frame>) -> String {
+        let mut out = vec![];
+        next.draw(previous, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_draw_first() {
+        let next = frame(&["3 > ", "one"], 0, 5);
+        assert_eq!(
+            draw(&next, None),
+            "\x1b[?2026h\x1b[1G\x1b[J\x1b[1G3 > \x1b[K\x1b[1B\x1b[1Gone\x1b[K\x1b[1A\x1b[5G\x1b[?2026l"
+        );
+    }
+
+    #[test]
+    fn test_draw_changes_only() {
+        let previous = frame(&["3 > ", "one", "two"], 0, 5);
+        let next = frame(&["3 > ", "one", "three"], 0, 5);
+        assert_eq!(
+            draw(&next, Some(&previous)),
+            "\x1b[?2026h\x1b[2B\x1b[1Gthree\x1b[K\x1b[2A\x1b[5G\x1b[?2026l"
+        );
+
+        assert_eq!(draw(&next, Some(&next)), "\x1b[?2026h\x1b[5G\x1b[?2026l");
+    }
+
+    #[test]
+    fn test_draw_fewer_rows() {
+        let previous = frame(&["one", "two", "3 > "], 2, 5);
+        let next = frame(&["one", "1 > t"], 1, 6);
+        assert_eq!(
+            draw(&next, Some(&previous)),
+            "\x1b[?2026h\x1b[1A\x1b[1G1 > t\x1b[K\x1b[1B\x1b[1G\x1b[2K\x1b[1A\x1b[6G\x1b[?2026l"
+        );
+    }
+}
@@ -1,10 +1,44 @@
-use std::io::{self, Write, BufWriter};
-
-use super::{MatchWithPositions, match_and_score_with_positions};
-use ansi::{clear, color, cursor, style};
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread::{self, Scope};
+use std::time::{Duration, Instant};
+
+use super::IndexedMatch;
+use cache::{self, Cache};
+use ansi::{clear, cursor, screen, style};
+use ansi::color::Depth;
+use ansi::parse::{span_at, Span};
+use display;
+use frame::Frame;
+use layout::{Layout, Row};
+use mode::{Mode, Query};
+use prefilter::Signature;
+use sort::{self, sort_top, Tiebreak};
+use stdin::InputLines;
+use theme::{Style, Theme};
 use terminal::{self, Terminal, Key, Event};
 
 use rayon::prelude::*;
+use unicode_width::UnicodeWidthStr;
+
+// The number of matches displayed, outside of fullscreen
+const MAX_DISPLAYED: usize = 10;
+
+// Roughly how many bytes of matches the cache of previous search terms may
+// hold: over a million matches, at 56 bytes each without their positions
+const MAX_CACHED: usize = 64 << 20;
+
+// How often to check on a search running in the background
+const POLL_INTERVAL: Duration = Duration::from_millis(15);
+
+// The frames of the spinner shown while searching, each shown for
+// SPINNER_INTERVAL; it's only shown once a search has taken SPINNER_DELAY, so
+// that quick searches don't flash it
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const SPINNER_INTERVAL: Duration = Duration::from_millis(80);
+const SPINNER_DELAY: Duration = Duration::from_millis(100);
 
 #[derive(Debug)]
 pub enum Error {
@@ -25,163 +59,613 @@ impl From<terminal::Error> for Error {
     }
 }
 
+/// Settings controlling how an `Interface` presents its matches.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Criteria used to order matches with equal scores
+    pub tiebreaks: Vec<Tiebreak>,
+    /// Whether matches are sorted by score, or kept in input order
+    pub sort: bool,
+    /// How the search term is matched against each line
+    pub mode: Mode,
+    /// Whether diacritics are ignored when matching
+    pub fold_accents: bool,
+    /// The number of columns between tab stops
+    pub tabstop: usize,
+    /// The colors used to draw each element
+    pub theme: Theme,
+    /// The arrangement of the prompt and matches
+    pub layout: Layout,
+    /// Whether to take over the whole terminal, using the alternate screen
+    pub fullscreen: bool,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            tiebreaks: vec![],
+            sort: true,
+            mode: Mode::Fuzzy,
+            fold_accents: false,
+            tabstop: 8,
+            theme: Theme::default(),
+            layout: Layout::default(),
+            fullscreen: false,
+        }
+    }
+}
+
+// A search running in the background, which sends its matches unless it's
+// cancelled first
+struct Pending<'a> {
+    key: cache::Key,
+    cancelled: Arc<AtomicBool>,
+    started: Instant,
+    receiver: Receiver<Vec<IndexedMatch<'a>>>,
+}
+
 pub struct Interface<'a> {
-    lines: &'a [String],
-    matches: Vec<MatchWithPositions<'a>>,
+    lines: &'a InputLines,
+    output: &'a InputLines,
+    styles: &'a [Vec<Span>],
+    signatures: &'a [Signature],
+    matches: Arc<Vec<IndexedMatch<'a>>>,
+    // what `matches` were found for
+    matched: cache::Key,
+    cache: Cache<IndexedMatch<'a>>,
+    options: Options,
 
     search: String,
+    query: Query,
+    error: Option<String>,
+    text_mode: Mode,
     selected: usize,
+    hscroll: isize,
+
+    pending: Option<Pending<'a>>,
 
     choices_width: usize,
     width: usize,
+    // the number of rows available for matches
+    height: usize,
+    // what was last drawn on screen
+    frame: Option<Frame>,
 
     terminal: Terminal,
 }
 
 impl<'a> Interface<'a> {
-    // Creates a new Interface with the provided lines
-    pub fn new(lines: &'a [String]) -> Interface<'a> {
+    // Creates a new Interface with the provided lines and options
+    pub fn new(lines: &'a InputLines, mut options: Options) -> Interface<'a> {
         let mut terminal = Terminal::from("/dev/tty").unwrap();
         let choices_width = format!("{}", lines.len()).len();
+        let mut height = terminal.max_height.saturating_sub(1);
+
+        if !options.fullscreen {
+            height = height.min(MAX_DISPLAYED);
+        }
 
         terminal.set_raw_mode().unwrap();
 
+        // only use colors the terminal can display
+        options.theme = options.theme.downgrade(Depth::detect());
+
+        // an empty regex is always valid
+        let query = Query::new(options.mode, "", options.fold_accents).unwrap();
+
+        // the mode to return to when regex mode is toggled off
+        let text_mode = match options.mode {
+            Mode::Regex => Mode::Fuzzy,
+            mode => mode
+        };
+
         Interface {
             lines: lines,
-            matches: vec![],
+            output: lines,
+            styles: &[],
+            signatures: &[],
+            matches: Arc::new(vec![]),
+            matched: cache::Key::new("", options.mode, options.fold_accents, options.sort),
+            cache: Cache::new(MAX_CACHED),
+            options,
             search: String::new(),
+            query,
+            error: None,
+            text_mode,
             selected: 0,
+            hscroll: 0,
+            pending: None,
             choices_width: choices_width,
             width: terminal.max_width,
+            height,
+            frame: None,
             terminal: terminal,
         }
     }
 
+    // Sets the lines printed in place of `lines` when selected, which must be
+    // in the same order
+    pub fn output(mut self, output: &'a InputLines) -> Interface<'a> {
+        self.output = output;
+        self
+    }
+
+    // Sets the SGR styling of each of `lines`, see `ansi::parse`
+    pub fn styles(mut self, styles: &'a [Vec<Span>]) -> Interface<'a> {
+        self.styles = styles;
+        self
+    }
+
+    // Sets the signature of each of `lines`, used to rule out lines quickly
+    // while searching
+    pub fn signatures(mut self, signatures: &'a [Signature]) -> Interface<'a> {
+        self.signatures = signatures;
+        self
+    }
+
     // Runs the Interface, returning either the final selection, or an error
     pub fn run(&mut self) -> Result<&str, Error> {
-        self.filter_matches();
+        if self.options.fullscreen {
+            write!(self.terminal, "{}{}", screen::ToAlternate, cursor::GoTo(1, 1))?;
+        } else {
+            self.reserve()?;
+        }
+
+        // searches run on threads borrowing `lines`, all of which are
+        // cancelled once the loop returns, however it does
+        thread::scope(|scope| {
+            let result = self.event_loop(scope);
+            self.cancel();
+            result
+        })?;
+
+        self.reset()?;
+        Ok(self.result())
+    }
+
+    // Handles input until a match is chosen, searching in the background so
+    // that typing is never held up
+    fn event_loop<'scope>(&mut self, scope: &'scope Scope<'scope, '_>) -> Result<(), Error>
+        where 'a: 'scope
+    {
+        let mut events = self.terminal.events()?;
+
+        self.find_matches(scope);
         self.render()?;
 
-        for event in self.terminal.events()? {
-            if let Event::Key(key) = event? {
-                match key {
-                    Key::Ctrl('c') | Key::Ctrl('d') | Key::Escape => {
-                        self.reset()?;
-                        return Err(Error::Exit);
+        loop {
+            // while searching, wake regularly to pick up the results and
+            // animate the spinner
+            let timeout = self.pending.as_ref().map(|_| POLL_INTERVAL);
+
+            if !events.pending() && !self.terminal.wait_for_input(timeout)? {
+                self.receive_matches(false);
+                self.render()?;
+                continue;
+            }
+
+            let key = match events.next() {
+                Some(event) => match event? {
+                    Event::Key(key) => key,
+                    _ => continue
+                },
+                None => break
+            };
+
+            match key {
+                Key::Ctrl('c') | Key::Ctrl('d') | Key::Escape => {
+                    self.cancel();
+                    self.reset()?;
+                    return Err(Error::Exit);
+                }
+
+                Key::Char('\n') => {
+                    // choose from the latest search term's matches
+                    self.receive_matches(true);
+                    break;
+                },
+
+                Key::Ctrl('n') => self.move_selected(1),
+                Key::Ctrl('p') => self.move_selected(-1),
+
+                Key::Left => {
+                    let step = (self.width / 4).max(1) as isize;
+                    self.scroll_selected(-step);
+                },
+
+                Key::Right => {
+                    let step = (self.width / 4).max(1) as isize;
+                    self.scroll_selected(step);
+                },
+
+                Key::Char(ch) => {
+                    self.search.push(ch);
+
+                    if self.compile() {
+                        self.find_matches(scope);
                     }
+                },
+
+                Key::Backspace | Key::Ctrl('h') => {
+                    self.search.pop();
 
-                    Key::Char('\n') => {
-                        break;
-                    },
-
-                    Key::Ctrl('n') => {
-                        self.selected += 1;
-                        self.render()?;
-                    },
-
-                    Key::Ctrl('p') => {
-                        self.selected = self.selected.saturating_sub(1);
-                        self.render()?;
-                    },
-
-                    Key::Char(ch) => {
-                        self.search.push(ch);
-                        self.filter_existing();
-                        self.render()?;
-                    },
-
-                    Key::Backspace | Key::Ctrl('h') => {
-                        self.search.pop();
-                        self.filter_matches();
-                        self.render()?;
+                    if self.compile() {
+                        self.find_matches(scope);
                     }
+                }
+
+                Key::Ctrl('u') => {
+                    self.search.clear();
 
-                    Key::Ctrl('u') => {
-                        self.search.clear();
-                        self.filter_matches();
-                        self.render()?;
+                    if self.compile() {
+                        self.find_matches(scope);
                     }
+                }
+
+                Key::Ctrl('r') => self.toggle_regex(scope),
+                Key::Ctrl('s') => self.toggle_sort(scope),
+
+                _ => continue
+            }
+
+            self.receive_matches(false);
+            self.render()?;
+        }
+
+        Ok(())
+    }
+
+    // Compiles `search` into `query`, returning whether it was valid. An
+    // invalid query leaves the previous one, and its matches, in place.
+    fn compile(&mut self) -> bool {
+        match Query::new(self.options.mode, &self.search, self.options.fold_accents) {
+            Ok(query) => {
+                self.query = query;
+                self.error = None;
+                true
+            },
+            Err(err) => {
+                // regex errors span several lines, the last describing the problem
+                let message = err.to_string();
+                let message = message.lines().last().unwrap_or("invalid regex");
+                self.error = Some(message.trim_start_matches("error: ").to_string());
+                false
+            }
+        }
+    }
+
+    // Finds the matches for `query`, restoring them from the cache if they
+    // were found before. Otherwise they're searched for in the background,
+    // among the fewest candidates known to contain them all.
+    fn find_matches<'scope>(&mut self, scope: &'scope Scope<'scope, '_>)
+        where 'a: 'scope
+    {
+        if self.restore_matches() {
+            return;
+        }
+
+        let key = self.key();
+        let mut candidates = self.cache.longest_prefix(&key);
+
+        // the current matches may not have fit in the cache
+        if self.pending.is_none() && key.narrows(&self.matched) {
+            let longer = candidates.as_ref().is_none_or(|cached| cached.len() > self.matches.len());
+
+            if longer {
+                candidates = Some(self.matches.clone());
+            }
+        }
+
+        self.spawn_search(scope, candidates);
+    }
+
+    // Restores the matches for `query` from the cache, returning whether
+    // they were cached
+    fn restore_matches(&mut self) -> bool {
+        let key = self.key();
+
+        match self.cache.get(&key) {
+            Some(matches) => {
+                self.cancel();
+                self.matches = matches;
+                self.matched = key;
+                self.hscroll = 0;
+                true
+            },
+            None => false
+        }
+    }
 
-                    _ => {}
+    // Identifies the matches for `query` in the cache
+    fn key(&self) -> cache::Key {
+        cache::Key::new(&self.search, self.options.mode, self.options.fold_accents, self.options.sort)
+    }
+
+    // Starts matching `query` against `candidates`, or all `lines`, on a
+    // background thread, cancelling any search already running. The current
+    // matches stay displayed until the new ones are received.
+    fn spawn_search<'scope>(&mut self, scope: &'scope Scope<'scope, '_>, candidates: Option<Arc<Vec<IndexedMatch<'a>>>>)
+        where 'a: 'scope
+    {
+        self.cancel();
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+
+        self.pending = Some(Pending {
+            key: self.key(),
+            cancelled: cancelled.clone(),
+            started: Instant::now(),
+            receiver,
+        });
+
+        let lines = self.lines;
+        let signatures = self.signatures;
+        let query = self.query.clone();
+        let options = self.options.clone();
+        let height = self.height;
+
+        scope.spawn(move || {
+            let found = match candidates {
+                Some(matches) => {
+                    let matches = matches.par_iter().map(|&(index, line, _, _)| (index, line, 0.0, vec![]));
+                    filter(&query, &options, matches, signatures, &cancelled)
+                },
+                None => {
+                    let lines = lines.par_enumerate().map(|(index, line)| (index, line, 0.0, vec![]));
+                    filter(&query, &options, lines, signatures, &cancelled)
                 }
             };
+
+            if let Some(mut matches) = found {
+                sort_matches(&mut matches, &query, &options, height);
+
+                // the receiver is gone if the search was replaced meanwhile
+                let _ = sender.send(matches);
+            }
+        });
+    }
+
+    // Cancels the search running in the background, if any
+    fn cancel(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            pending.cancelled.store(true, Ordering::Relaxed);
         }
+    }
 
-        self.reset()?;
-        Ok(self.result())
+    // Replaces `matches` with those of the search running in the background,
+    // if it has finished, or has once `wait` is set. A search whose thread
+    // panicked is given up on, leaving the previous matches in place.
+    fn receive_matches(&mut self, wait: bool) {
+        let received = match self.pending {
+            Some(ref pending) if wait => pending.receiver.recv().map_err(|_| TryRecvError::Disconnected),
+            Some(ref pending) => pending.receiver.try_recv(),
+            None => return
+        };
+
+        match received {
+            Ok(matches) => {
+                let pending = self.pending.take().unwrap();
+
+                self.matches = Arc::new(matches);
+                self.matched = pending.key;
+                self.cache.insert(self.matched.clone(), self.matches.clone());
+                self.hscroll = 0;
+            },
+            Err(TryRecvError::Empty) => {},
+            Err(TryRecvError::Disconnected) => self.pending = None
+        }
     }
 
-    // Matches and scores `lines` by `search`, sorting the result
-    fn filter_matches(&mut self) {
-        let ref search = self.search;
+    // Switches between sorting matches by score and keeping input order
+    fn toggle_sort<'scope>(&mut self, scope: &'scope Scope<'scope, '_>)
+        where 'a: 'scope
+    {
+        self.options.sort = !self.options.sort;
 
-        self.matches = self.lines.
-            par_iter().
-            filter_map(|line| match_and_score_with_positions(search, line)).
-            collect();
+        if self.restore_matches() {
+            return;
+        }
 
-        self.matches.par_sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap().reverse());
+        if self.pending.is_some() {
+            self.spawn_search(scope, None);
+        } else if self.options.sort {
+            // unsorted matches were never scored
+            let candidates = self.matches.clone();
+            self.spawn_search(scope, Some(candidates));
+        } else {
+            let matches = Arc::make_mut(&mut self.matches);
+            matches.par_sort_by_key(|choice| choice.0);
+            sort_matches(matches, &self.query, &self.options, self.height);
+
+            self.matched = self.key();
+            self.cache.insert(self.key(), self.matches.clone());
+            self.hscroll = 0;
+        }
     }
 
-    // Matches and scores the existing `matches` by `search`, sorting the result
-    fn filter_existing(&mut self) {
-        let ref search = self.search;
+    // Switches between regex mode and the mode the Interface started in
+    fn toggle_regex<'scope>(&mut self, scope: &'scope Scope<'scope, '_>)
+        where 'a: 'scope
+    {
+        self.options.mode = match self.options.mode {
+            Mode::Regex => self.text_mode,
+            _ => Mode::Regex
+        };
+
+        if self.compile() {
+            self.find_matches(scope);
+        }
+    }
 
-        self.matches = self.matches.
-            par_iter().
-            filter_map(|&(line, _, _)| match_and_score_with_positions(search, line)).
-            collect();
+    // Scrolls the selected match `delta` columns further than needed to show
+    // its matched text, without scrolling past either end of the line
+    fn scroll_selected(&mut self, delta: isize) {
+        if let Some(choice) = self.matches.get(self.selected) {
+            let cells = display::cells(choice.1, &choice.3, self.options.tabstop);
+            let offset = display::match_offset(&cells, self.width) as isize;
+            let max = display::max_offset(&cells, self.width) as isize;
 
-        self.matches.par_sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap().reverse());
+            self.hscroll = (offset + self.hscroll + delta).max(0).min(max) - offset;
+        }
     }
 
-    // Renders the current state of the Interface to it's `terminal`
+    // Renders the current state of the Interface to it's `terminal`, only
+    // redrawing the rows that changed since the last render
     fn render(&mut self) -> io::Result<()> {
         self.clamp_selected();
 
-        let prompt = self.prompt();
-        let matches = self.matches.iter().take(10);
-        let n = matches.len() as u16;
+        let displayed = self.matches.len().min(self.height);
+        let layout = self.options.layout.rows(displayed, self.height);
+        let mut rows = Vec::with_capacity(layout.len());
+
+        for row in &layout {
+            let mut text = vec![];
+
+            match *row {
+                Row::Prompt => self.render_prompt(&mut text)?,
+                Row::Match(index) => self.render_match(&mut text, index)?,
+                Row::Blank => {}
+            }
+
+            rows.push(text);
+        }
+
+        let frame = Frame {
+            rows,
+            cursor_row: layout.iter().position(|&row| row == Row::Prompt).unwrap_or(0),
+            cursor_column: (self.prompt().width() + 1) as u16,
+        };
+
+        if self.frame.as_ref() == Some(&frame) {
+            return Ok(());
+        }
+
+        // write the whole frame at once, so it's never seen half drawn
+        let mut out = vec![];
+        frame.draw(self.frame.as_ref(), &mut out)?;
+        self.frame = Some(frame);
+
+        self.terminal.write_all(&out)?;
+        self.terminal.flush()
+    }
 
-        let mut term = BufWriter::new(&mut self.terminal);
+    // Writes the prompt, and any error with the search term
+    fn render_prompt<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let theme = &self.options.theme;
+        let count = format!("{:width$}", self.matches.len(), width = self.choices_width);
 
-        write!(term, "{}{}{}", cursor::Column(1), clear::Screen, prompt)?;
+        write_styled(out, theme.counter, &count)?;
+        write!(out, " ")?;
+        write_styled(out, theme.prompt, ">")?;
+        write!(out, " {}", self.search)?;
 
-        for (i, choice) in matches.enumerate() {
-            let selected = i == self.selected;
-            let chars = choice.0.chars().take(self.width);
+        if let Some(ref error) = self.error {
+            write!(out, "  ")?;
+            write_styled(out, theme.error, &format!("[{}]", error))?;
+        }
 
-            write!(term, "\r\n")?;
+        if let Some(ref pending) = self.pending {
+            let elapsed = pending.started.elapsed();
 
-            if selected {
-                write!(term, "{}", style::Invert)?;
+            if elapsed >= SPINNER_DELAY {
+                let frame = (elapsed.as_millis() / SPINNER_INTERVAL.as_millis()) as usize;
+                write!(out, "  ")?;
+                write_styled(out, theme.counter, &SPINNER[frame % SPINNER.len()].to_string())?;
             }
+        }
 
-            let ref positions = choice.2;
+        Ok(())
+    }
 
-            for (i, ch) in chars.enumerate() {
-                if positions.contains(&i) {
-                    let color = color::Fg(color::Colors::Magenta);
-                    let reset = color::Fg(color::Reset);
-                    write!(term, "{}{}{}", color, ch, reset)?;
-                } else {
-                    write!(term, "{}", ch)?;
-                }
+    // Writes the match at `index`, fitted to the terminal's width
+    fn render_match<W: Write>(&self, out: &mut W, index: usize) -> io::Result<()> {
+        let theme = &self.options.theme;
+        let choice = &self.matches[index];
+        let selected = index == self.selected;
+
+        let cells = display::cells(choice.1, &choice.3, self.options.tabstop);
+        let mut offset = display::match_offset(&cells, self.width);
+
+        if selected {
+            offset = (offset as isize + self.hscroll) as usize;
+        }
+
+        let cells = display::scroll(cells, offset, self.width);
+
+        let (line_style, highlight) = if selected {
+            (theme.selected, theme.selected_highlight)
+        } else {
+            (Style::default(), theme.highlight)
+        };
+
+        write!(out, "{}", line_style)?;
+
+        let spans = self.styles.get(choice.0).map(Vec::as_slice).unwrap_or(&[]);
+        let mut active = "";
+
+        for cell in cells {
+            // markers aren't part of the line, so take none of its styling
+            let sgr = cell.index.
+                and_then(|index| span_at(spans, index)).
+                map(|span| span.sgr.as_str()).
+                unwrap_or("");
+
+            if sgr != active {
+                write!(out, "{}{}{}", style::Reset, line_style, sgr)?;
+                active = sgr;
             }
 
-            if selected {
-                write!(term, "{}", style::NoInvert)?;
+            let accent = if cell.index.is_none() {
+                Some(theme.marker)
+            } else if cell.highlighted {
+                Some(highlight)
+            } else {
+                None
+            };
+
+            match accent {
+                Some(accent) => {
+                    write!(out, "{}{}", accent, cell.text)?;
+                    write!(out, "{}{}{}", style::Reset, line_style, active)?;
+                },
+                None => write!(out, "{}", cell.text)?
             }
         }
 
-        if n > 0 {
-            let col = (prompt.len() + 1) as u16;
-            write!(term, "{}{}", cursor::Up(n), cursor::Column(col))?;
+        if !line_style.is_plain() || !active.is_empty() {
+            write!(out, "{}", style::Reset)?;
+        }
+
+        Ok(())
+    }
+
+    // Scrolls the screen if there isn't room below the cursor for every row
+    // of the interface, leaving the cursor on the first of them
+    fn reserve(&mut self) -> io::Result<()> {
+        let rows = self.height;
+
+        let fits = match self.terminal.cursor_position() {
+            Ok((row, _)) => row + rows <= self.terminal.max_height,
+            // without knowing where the cursor is, make room regardless
+            Err(_) => false
+        };
+
+        if !fits && rows > 0 {
+            write!(self.terminal, "{}{}", "\n".repeat(rows), cursor::Up(rows as u16))?;
         }
 
         Ok(())
     }
 
+    // Moves the selection `delta` rows down the screen
+    fn move_selected(&mut self, delta: isize) {
+        let delta = if self.options.layout.is_upwards() { -delta } else { delta };
+
+        self.selected = (self.selected as isize + delta).max(0) as usize;
+        self.hscroll = 0;
+    }
+
     // Generates the input prompt
     fn prompt(&self) -> String {
         let count = self.matches.len();
@@ -191,24 +675,104 @@ impl<'a> Interface<'a> {
     // Clamps `selected`, such that it doesn't overflow the matches length
     fn clamp_selected(&mut self) {
         let mut max = self.matches.len();
-        if max > 10 { max = 10; }
+        if max > self.height { max = self.height; }
 
         if self.selected >= max {
             self.selected = if max > 0 { max - 1 } else { 0 };
         }
     }
 
-    // Resets the `terminal`
+    // Resets the `terminal`, erasing the interface or, in fullscreen, leaving
+    // the alternate screen for the original one
     fn reset(&mut self) -> Result<(), Error> {
-        write!(self.terminal, "{}{}", cursor::Column(1), clear::Screen)?;
+        if self.options.fullscreen {
+            write!(self.terminal, "{}", screen::ToMain)?;
+        } else {
+            let row = self.frame.as_ref().map_or(0, |frame| frame.cursor_row);
+
+            if row > 0 {
+                write!(self.terminal, "{}", cursor::Up(row as u16))?;
+            }
+
+            write!(self.terminal, "{}{}", cursor::Column(1), clear::Screen)?;
+        }
+
         self.terminal.reset()?;
         Ok(())
     }
 
     fn result(&mut self) -> &str {
-        self.matches.iter().
-            nth(self.selected).
-            map(|choice| choice.0).
+        self.matches.
+            get(self.selected).
+            map(|choice| &self.output[choice.0]).
             unwrap_or(&self.search)
     }
 }
+
+// Writes `text` in `style`, resetting it afterwards
+fn write_styled<W: Write>(out: &mut W, style: Style, text: &str) -> io::Result<()> {
+    if style.is_plain() {
+        write!(out, "{}", text)
+    } else {
+        write!(out, "{}{}{}", style, text, style::Reset)
+    }
+}
+
+// Matches `candidates` by `query`, skipping those whose signatures rule them
+// out, and giving up with `None` as soon as the search is `cancelled`
+fn filter<'a, I>(query: &Query, options: &Options, candidates: I, signatures: &[Signature], cancelled: &AtomicBool) -> Option<Vec<IndexedMatch<'a>>>
+    where I: ParallelIterator<Item = IndexedMatch<'a>>
+{
+    let sorted = options.sort;
+
+    let matches: Vec<_> = candidates.
+        map(|(index, line, _, _)| {
+            if cancelled.load(Ordering::Relaxed) {
+                None
+            } else if !signatures.get(index).is_none_or(|&signature| query.may_match(signature)) {
+                Some(None)
+            } else {
+                Some(match_line(query, sorted, index, line))
+            }
+        }).
+        while_some().
+        flatten().
+        collect();
+
+    if cancelled.load(Ordering::Relaxed) {
+        None
+    } else {
+        Some(matches)
+    }
+}
+
+// Moves the best `height` matches to the front of `matches`. Only a
+// screenful is ever displayed, so the remainder is left unsorted.
+//
+// When sorting is disabled, `matches` is already in input order, and only
+// the displayed matches need scoring for their positions.
+fn sort_matches(matches: &mut [IndexedMatch], query: &Query, options: &Options, height: usize) {
+    if options.sort {
+        let tiebreaks = &options.tiebreaks;
+        sort_top(matches, height, |a, b| sort::compare(tiebreaks, a, b));
+    } else {
+        for choice in matches.iter_mut().take(height) {
+            let (score, positions) = query.score_with_positions(choice.1);
+            choice.2 = score;
+            choice.3 = positions;
+        }
+    }
+}
+
+// Matches `line` by `query`. Unsorted matches skip scoring entirely, as it's
+// only needed for the few that are displayed.
+fn match_line<'a>(query: &Query, sorted: bool, index: usize, line: &'a str) -> Option<IndexedMatch<'a>> {
+    if sorted {
+        query.match_and_score_with_positions(line).
+            map(|(line, score, positions)| (index, line, score, positions))
+    } else if query.matches(line) {
+        Some((index, line, 0.0, vec![]))
+    } else {
+        None
+    }
+}
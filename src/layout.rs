@@ -0,0 +1,108 @@
+use std::str::FromStr;
+
+/// The arrangement of the prompt and matches on screen.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Layout {
+    /// The prompt on top, with the best match directly below it
+    #[default]
+    Default,
+    /// The prompt at the bottom, with the best match directly above it
+    Reverse,
+    /// The prompt at the bottom, with the matches above it listed best first
+    ReverseList,
+}
+
+/// A single row of the interface.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Row {
+    Prompt,
+    /// The match at this index among those displayed
+    Match(usize),
+    Blank,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Layout, String> {
+        match s {
+            "default" => Ok(Layout::Default),
+            "reverse" => Ok(Layout::Reverse),
+            "reverse-list" => Ok(Layout::ReverseList),
+            _ => Err(format!("'{}' is not a valid layout", s))
+        }
+    }
+}
+
+impl Layout {
+    /// The rows of the interface from top to bottom, for `matches` matches
+    /// displayed in space reserved for `height` of them.
+    ///
+    /// Layouts with the prompt at the bottom always fill the reserved space,
+    /// so the prompt stays in place as the number of matches changes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rff::layout::{Layout, Row};
+    ///
+    /// let rows = Layout::Reverse.rows(2, 3);
+    /// assert_eq!(rows, vec![Row::Blank, Row::Match(1), Row::Match(0), Row::Prompt]);
+    /// ```
+    pub fn rows(&self, matches: usize, height: usize) -> Vec<Row> {
+        let matches = matches.min(height);
+        let blanks = (0..height - matches).map(|_| Row::Blank);
+
+        match *self {
+            Layout::Default => {
+                Some(Row::Prompt).into_iter().
+                    chain((0..matches).map(Row::Match)).
+                    collect()
+            },
+            Layout::Reverse => {
+                blanks.
+                    chain((0..matches).rev().map(Row::Match)).
+                    chain(Some(Row::Prompt)).
+                    collect()
+            },
+            Layout::ReverseList => {
+                blanks.
+                    chain((0..matches).map(Row::Match)).
+                    chain(Some(Row::Prompt)).
+                    collect()
+            },
+        }
+    }
+
+    /// Whether the list runs upwards from the prompt, so moving up the screen
+    /// moves to worse matches.
+    pub fn is_upwards(&self) -> bool {
+        *self == Layout::Reverse
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_from_str() {
+        assert_eq!("default".parse(), Ok(Layout::Default));
+        assert_eq!("reverse".parse(), Ok(Layout::Reverse));
+        assert_eq!("reverse-list".parse(), Ok(Layout::ReverseList));
+        assert!("inline".parse::<Layout>().is_err());
+    }
+
+    #[test]
+    fn test_rows() {
+        assert_eq!(Layout::Default.rows(2, 3), vec![Row::Prompt, Row::Match(0), Row::Match(1)]);
+        assert_eq!(Layout::ReverseList.rows(2, 3), vec![Row::Blank, Row::Match(0), Row::Match(1), Row::Prompt]);
+        assert_eq!(Layout::Reverse.rows(0, 1), vec![Row::Blank, Row::Prompt]);
+    }
+
+    #[test]
+    fn test_rows_overflow() {
+        assert_eq!(Layout::Default.rows(5, 2), vec![Row::Prompt, Row::Match(0), Row::Match(1)]);
+        assert_eq!(Layout::Reverse.rows(5, 2), vec![Row::Match(1), Row::Match(0), Row::Prompt]);
+    }
+}
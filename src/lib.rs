@@ -1,19 +1,39 @@
 extern crate libc;
 extern crate rayon;
+extern crate regex;
+extern crate unicode_normalization;
+extern crate unicode_segmentation;
+extern crate unicode_width;
 
 mod consts;
+#[cfg(test)]
+mod random;
 mod terminal;
 
 pub mod ansi;
+pub mod cache;
+pub mod display;
+pub mod frame;
+pub mod layout;
 pub mod stdin;
 pub mod matcher;
 pub mod matrix;
+pub mod mmap;
+pub mod mode;
+pub mod normalize;
+pub mod prefilter;
 pub mod scorer;
+pub mod simd;
+pub mod sort;
+pub mod theme;
 pub mod interface;
 
 pub type Match<'a> = (&'a str, f64);
 pub type MatchWithPositions<'a> = (&'a str, f64, Vec<usize>);
 
+/// A match along with the index of its line in the input.
+pub type IndexedMatch<'a> = (usize, &'a str, f64, Vec<usize>);
+
 pub fn match_and_score<'a>(needle: &str, haystack: &'a str) -> Option<Match<'a>> {
     if matcher::matches(needle, haystack) {
         Some((haystack, scorer::score(needle, haystack)))
@@ -3,8 +3,15 @@ extern crate clap;
 extern crate rayon;
 
 use std::io::{self, Write, BufWriter};
-use rff::{stdin, match_and_score};
-use rff::interface::{Interface, Error};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use rff::stdin::{self, InputLines};
+use rff::ansi::parse::{self, Span};
+use rff::mode::{Mode, Query};
+use rff::prefilter::Signature;
+use rff::sort::{self, sort_top, Tiebreak};
+use rff::theme::{self, Theme};
+use rff::interface::{Interface, Options, Error};
 use clap::{App, Arg};
 use rayon::prelude::*;
 
@@ -25,13 +32,132 @@ fn run() -> i32 {
                 value_name("QUERY").
                 help("Term to search for")
         ).
+        arg(
+            Arg::with_name("limit").
+                short("l").
+                long("limit").
+                value_name("N").
+                validator(is_number).
+                help("Print at most N matches with -s/--search")
+        ).
+        arg(
+            Arg::with_name("input").
+                short("i").
+                long("input").
+                value_name("FILE").
+                help("Read choices from FILE rather than stdin, mapping it into memory")
+        ).
+        arg(
+            Arg::with_name("tiebreak").
+                long("tiebreak").
+                value_name("CRITERIA").
+                use_delimiter(true).
+                possible_values(&["index", "length", "begin", "end", "depth"]).
+                help("Comma-separated criteria used to order matches with equal scores")
+        ).
+        arg(
+            Arg::with_name("exact").
+                short("e").
+                long("exact").
+                conflicts_with_all(&["prefix", "regex", "typos"]).
+                help("Match the search term as a contiguous substring")
+        ).
+        arg(
+            Arg::with_name("prefix").
+                long("prefix").
+                conflicts_with_all(&["regex", "typos"]).
+                help("Only match lines beginning with the search term")
+        ).
+        arg(
+            Arg::with_name("regex").
+                long("regex").
+                conflicts_with("typos").
+                help("Match the search term as a regular expression")
+        ).
+        arg(
+            Arg::with_name("typos").
+                long("typos").
+                help("Tolerate a few misspelled or missing chars in the search term")
+        ).
+        arg(
+            Arg::with_name("fold-accents").
+                long("fold-accents").
+                help("Ignore diacritics when matching, so 'cafe' matches 'café'")
+        ).
+        arg(
+            Arg::with_name("no-sort").
+                long("no-sort").
+                help("Keep matches in input order, rather than sorting by score")
+        ).
+        arg(
+            Arg::with_name("tabstop").
+                long("tabstop").
+                value_name("N").
+                validator(is_number).
+                help("Number of columns between tab stops [default: 8]")
+        ).
+        arg(
+            Arg::with_name("layout").
+                long("layout").
+                value_name("LAYOUT").
+                possible_values(&["default", "reverse", "reverse-list"]).
+                help("Draw the prompt on top, or at the bottom with the best match nearest it (reverse) or topmost (reverse-list)")
+        ).
+        arg(
+            Arg::with_name("fullscreen").
+                long("fullscreen").
+                help("Use the whole terminal, restoring its contents on exit")
+        ).
+        arg(
+            Arg::with_name("color").
+                long("color").
+                value_name("SPEC").
+                validator(|spec| spec.parse::<Theme>().map(|_| ())).
+                help("Colors to draw with: a dark or light preset and/or element:color pairs, e.g. 'light,highlight:#ff8700'")
+        ).
+        arg(
+            Arg::with_name("no-color").
+                long("no-color").
+                visible_alias("mono").
+                help("Draw without colors, marking matches with bold and underline [env: NO_COLOR]")
+        ).
+        arg(
+            Arg::with_name("ansi").
+                long("ansi").
+                help("Match input lines with ANSI escape sequences removed, rendering their colors")
+        ).
+        arg(
+            Arg::with_name("strip-ansi").
+                long("strip-ansi").
+                requires("ansi").
+                help("Print matches with ANSI escape sequences removed")
+        ).
         arg(
             Arg::with_name("benchmark").
                 long("benchmark").
-                help("Run rff in benchmark mode")
+                help("Time each phase of searching for -s/--search, running it repeatedly")
         ).
         get_matches();
 
+    let tiebreaks: Vec<Tiebreak> = matches.values_of("tiebreak").
+        map(|values| values.map(|v| v.parse().unwrap()).collect()).
+        unwrap_or_default();
+
+    let mode = if matches.is_present("exact") {
+        Mode::Exact
+    } else if matches.is_present("prefix") {
+        Mode::Prefix
+    } else if matches.is_present("regex") {
+        Mode::Regex
+    } else if matches.is_present("typos") {
+        Mode::Typos
+    } else {
+        Mode::Fuzzy
+    };
+
+    let fold_accents = matches.is_present("fold-accents");
+    let sorted = !matches.is_present("no-sort");
+
     let has_query = matches.is_present("query");
     let has_benchmark = matches.is_present("benchmark");
 
@@ -40,55 +166,259 @@ fn run() -> i32 {
         return 1
     }
 
+    let mono = matches.is_present("no-color") ||
+        (!matches.is_present("color") && theme::colors_disabled());
+
+    let path = matches.value_of("input");
+
+    // without colors, matches are printed without their escape sequences too
+    let keep_original = !mono && !matches.is_present("strip-ansi");
+
+    let mut input = match Input::read(path, matches.is_present("ansi"), keep_original) {
+        Ok(input) => input,
+        Err(error) => {
+            eprintln!("{}: {}", path.unwrap_or("stdin"), error);
+            return 1
+        }
+    };
+
+    // the colors of ANSI input are dropped along with our own
+    if mono {
+        input.styles.clear();
+    }
+
     if has_query {
-        let query = matches.value_of("query").unwrap();
+        let query = match Query::new(mode, matches.value_of("query").unwrap(), fold_accents) {
+            Ok(query) => query,
+            Err(error) => {
+                eprintln!("{}", error);
+                return 1
+            }
+        };
+
+        let limit = matches.value_of("limit").map(|n| n.parse().unwrap());
 
         if has_benchmark {
-            benchmark(query);
+            benchmark(&input, &query);
+        } else if sorted {
+            search(&input, &query, limit, &tiebreaks);
         } else {
-            search(query);
+            filter(&input, &query, limit);
         }
 
         return 0
     } else {
-        return interactive();
+        let mut options = Options {
+            tiebreaks,
+            sort: sorted,
+            mode,
+            fold_accents,
+            fullscreen: matches.is_present("fullscreen"),
+            ..Options::default()
+        };
+
+        if let Some(tabstop) = matches.value_of("tabstop") {
+            options.tabstop = tabstop.parse().unwrap();
+        }
+
+        if let Some(layout) = matches.value_of("layout") {
+            options.layout = layout.parse().unwrap();
+        }
+
+        // an explicit --color takes precedence over NO_COLOR
+        if mono {
+            options.theme = Theme::mono();
+        } else if let Some(spec) = matches.value_of("color") {
+            options.theme = spec.parse().unwrap();
+        }
+
+        return interactive(&input, options);
     }
 }
 
-fn benchmark(needle: &str) {
-    let lines = stdin::slurp();
+// Lines read from stdin or a file, with any ANSI escape sequences removed for matching
+struct Input {
+    lines: InputLines,
+    // the lines as read, if they differ from `lines` and should be printed
+    original: Option<InputLines>,
+    styles: Vec<Vec<Span>>,
+    signatures: Vec<Signature>,
+}
+
+impl Input {
+    fn read(path: Option<&str>, ansi: bool, keep_original: bool) -> io::Result<Input> {
+        let lines = match path {
+            // SAFETY: like other tools searching files in place, rff relies
+            // on nothing modifying the file while it runs
+            Some(path) => unsafe { stdin::map_file(Path::new(path))? },
+            None => stdin::slurp()
+        };
+
+        let mut input = if ansi {
+            let (stripped, styles): (Vec<_>, _) = lines.par_iter().map(parse::parse).unzip();
+
+            Input {
+                lines: stripped.into_iter().collect(),
+                original: if keep_original { Some(lines) } else { None },
+                styles,
+                signatures: vec![],
+            }
+        } else {
+            Input { lines, original: None, styles: vec![], signatures: vec![] }
+        };
 
-    // in benchmark mode, we run the match/score/sort loop 100 times
-    for _ in 0..100 {
-        lines
+        input.signatures = input.lines.par_iter().map(Signature::of).collect();
+        Ok(input)
+    }
+
+    // Whether the line at `index` could match `query`, judging by its
+    // signature alone
+    fn may_match(&self, query: &Query, index: usize) -> bool {
+        query.may_match(self.signatures[index])
+    }
+
+    // The line to print for the line at `index`
+    fn output(&self, index: usize) -> &str {
+        match self.original {
+            Some(ref original) => &original[index],
+            None => &self.lines[index]
+        }
+    }
+}
+
+// How many times --benchmark runs the match/score/sort loop
+const BENCHMARK_RUNS: u32 = 100;
+
+fn benchmark(input: &Input, query: &Query) {
+    let lines = &input.lines;
+
+    let mut matching = Duration::default();
+    let mut scoring = Duration::default();
+    let mut sorting = Duration::default();
+    let mut matched = 0;
+
+    for _ in 0..BENCHMARK_RUNS {
+        let start = Instant::now();
+
+        let found: Vec<_> = lines
+            .par_enumerate()
+            .filter(|&(index, line)| input.may_match(query, index) && query.matches(line))
+            .map(|(_, line)| line)
+            .collect();
+
+        let matched_at = Instant::now();
+
+        let mut scores: Vec<_> = found
             .par_iter()
-            .filter_map(|line| match_and_score(needle, line))
-            .collect::<Vec<_>>()
-            .par_sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap().reverse());
+            .map(|&line| (line, query.score(line)))
+            .collect();
+
+        let scored_at = Instant::now();
+
+        scores.par_sort_by(|a, b| sort::compare_scores(a.1, b.1));
+
+        matching += matched_at - start;
+        scoring += scored_at - matched_at;
+        sorting += scored_at.elapsed();
+        matched = found.len();
+    }
+
+    let bytes: usize = lines.par_iter().map(str::len).sum();
+
+    println!("{} lines ({:.1} MB), {} matched, {} runs", lines.len(), megabytes(bytes), matched, BENCHMARK_RUNS);
+    report_phase("match", matching, lines.len(), Some(bytes));
+    report_phase("score", scoring, matched, None);
+    report_phase("sort", sorting, matched, None);
+    report_phase("total", matching + scoring + sorting, lines.len(), Some(bytes));
+}
+
+// Prints the average time a phase of --benchmark took per run, and how many
+// lines (and bytes) per second it got through
+fn report_phase(name: &str, total: Duration, lines: usize, bytes: Option<usize>) {
+    let run = total / BENCHMARK_RUNS;
+    let seconds = run.as_secs_f64().max(f64::MIN_POSITIVE);
+
+    print!("{:<6} {:>10.3} ms/run  {:>10.2}M lines/s", name, seconds * 1000.0, lines as f64 / seconds / 1e6);
+
+    match bytes {
+        Some(bytes) => println!("  {:>10.1} MB/s", megabytes(bytes) / seconds),
+        None => println!()
     }
 }
 
-fn search(needle: &str) {
-    let lines = stdin::slurp();
+fn megabytes(bytes: usize) -> f64 {
+    bytes as f64 / (1 << 20) as f64
+}
+
+fn search(input: &Input, query: &Query, limit: Option<usize>, tiebreaks: &[Tiebreak]) {
+    let lines = &input.lines;
+
+    // positions are only worth computing if a tiebreak needs them
+    let with_positions = tiebreaks.iter().any(Tiebreak::needs_positions);
+
     let mut lines: Vec<_> = lines
-        .par_iter()
-        .filter_map(|line| match_and_score(needle, line))
+        .par_enumerate()
+        .filter(|&(index, _)| input.may_match(query, index))
+        .filter_map(|(index, line)| {
+            if with_positions {
+                query.match_and_score_with_positions(line)
+                    .map(|(line, score, positions)| (index, line, score, positions))
+            } else {
+                query.match_and_score(line)
+                    .map(|(line, score)| (index, line, score, vec![]))
+            }
+        })
         .collect();
 
-    lines.par_sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap().reverse());
+    let compare = |a: &_, b: &_| sort::compare(tiebreaks, a, b);
+
+    match limit {
+        Some(limit) => {
+            sort_top(&mut lines, limit, compare);
+            lines.truncate(limit);
+        },
+        None => lines.par_sort_by(compare)
+    }
 
     let stdout = io::stdout();
     let mut stdout = BufWriter::new(stdout.lock());
 
     for line in &lines {
-        writeln!(stdout, "{}", line.0).unwrap();
+        writeln!(stdout, "{}", input.output(line.0)).unwrap();
     }
 }
 
-fn interactive() -> i32 {
-    let lines = stdin::slurp();
+fn filter(input: &Input, query: &Query, limit: Option<usize>) {
+    let lines: Vec<_> = input.lines
+        .par_enumerate()
+        .filter(|&(index, line)| input.may_match(query, index) && query.matches(line))
+        .map(|(index, _)| index)
+        .collect();
+
+    let stdout = io::stdout();
+    let mut stdout = BufWriter::new(stdout.lock());
+
+    for &index in lines.iter().take(limit.unwrap_or(usize::MAX)) {
+        writeln!(stdout, "{}", input.output(index)).unwrap();
+    }
+}
+
+fn is_number(value: String) -> Result<(), String> {
+    value.parse::<usize>().
+        map(|_| ()).
+        map_err(|_| format!("'{}' is not a valid number", value))
+}
+
+fn interactive(input: &Input, options: Options) -> i32 {
+    let mut interface = Interface::new(&input.lines, options).
+        styles(&input.styles).
+        signatures(&input.signatures);
+
+    if let Some(ref original) = input.original {
+        interface = interface.output(original);
+    }
 
-    match Interface::new(&lines).run() {
+    match interface.run() {
         Ok(result) => println!("{}", result),
         Err(Error::Exit) => { return 1 },
         Err(error) => {
@@ -9,6 +9,16 @@
 pub fn matches(needle: &str, haystack: &str) -> bool {
     if needle == "" { return true; }
 
+    // ASCII chars only ever equal ASCII chars, and every byte of any other
+    // char is non-ASCII, so an ASCII needle can be compared bytewise
+    if needle.is_ascii() {
+        let mut hbytes = haystack.bytes();
+
+        return needle.bytes().all(|n| {
+            hbytes.any(|h| eq_ascii(n, h))
+        });
+    }
+
     let mut hchars = haystack.chars();
 
     needle.chars().all(|n| {
@@ -16,6 +26,133 @@ pub fn matches(needle: &str, haystack: &str) -> bool {
     })
 }
 
+/// Searches for needle as a contiguous run of chars in the haystack
+///
+/// # Examples
+///
+/// ```
+/// assert!(rff::matcher::matches_exact("models", "app/models/order"));
+/// assert!(!rff::matcher::matches_exact("amo", "app/models/order"));
+/// ```
+#[inline]
+pub fn matches_exact(needle: &str, haystack: &str) -> bool {
+    if needle.is_empty() { return true; }
+
+    occurrences(needle, haystack).next().is_some()
+}
+
+/// Checks whether the haystack begins with needle
+///
+/// # Examples
+///
+/// ```
+/// assert!(rff::matcher::matches_prefix("app", "app/models/order"));
+/// assert!(!rff::matcher::matches_prefix("models", "app/models/order"));
+/// ```
+#[inline]
+pub fn matches_prefix(needle: &str, haystack: &str) -> bool {
+    starts_with(needle, haystack)
+}
+
+/// Returns the char offsets at which needle occurs contiguously in the
+/// haystack.
+pub fn occurrences<'a>(needle: &'a str, haystack: &'a str) -> impl Iterator<Item = usize> + 'a {
+    haystack.char_indices().
+        enumerate().
+        filter(move |&(_, (offset, _))| starts_with(needle, &haystack[offset..])).
+        map(|(i, _)| i)
+}
+
+fn starts_with(needle: &str, haystack: &str) -> bool {
+    if needle.is_ascii() {
+        return haystack.len() >= needle.len() &&
+            needle.bytes().zip(haystack.bytes()).all(|(n, h)| eq_ascii(n, h));
+    }
+
+    let mut hchars = haystack.chars();
+
+    needle.chars().all(|n| {
+        hchars.next().is_some_and(|h| eq(n, h))
+    })
+}
+
+/// The number of typos tolerated in a needle of `length` chars.
+#[inline]
+pub fn max_typos(length: usize) -> usize {
+    length / 4
+}
+
+/// Searches for needle's chars in the haystack, tolerating a few of them being
+/// misspelled, transposed or missing (see `max_typos`).
+///
+/// Each needle char that can't be found costs one typo, so a substitution or
+/// omission costs one, as does a transposition (one of the swapped chars is
+/// still found in order).
+///
+/// Returns the indices within needle of the chars that were found, using the
+/// fewest typos possible.
+///
+/// # Examples
+///
+/// ```
+/// let found = rff::matcher::matches_with_typos("ordre", "order.rb");
+/// assert_eq!(found, Some(vec![0, 1, 2, 4]));
+/// ```
+pub fn matches_with_typos(needle: &str, haystack: &str) -> Option<Vec<usize>> {
+    let needle: Vec<char> = needle.chars().collect();
+    let haystack: Vec<char> = haystack.chars().collect();
+
+    let length = needle.len();
+    let max_errors = max_typos(length);
+
+    // ends[e][i] is the fewest haystack chars needed to find needle[..i] with
+    // exactly `e` typos, and skipped[e][i] whether needle[i - 1] was a typo
+    let mut ends = vec![vec![None; length + 1]; max_errors + 1];
+    let mut skipped = vec![vec![false; length + 1]; max_errors + 1];
+
+    for e in 0..(max_errors + 1) {
+        ends[e][0] = Some(0);
+
+        for i in 0..length {
+            let found = ends[e][i].and_then(|start: usize| {
+                haystack[start..].
+                    iter().
+                    position(|&h| eq(needle[i], h)).
+                    map(|offset| start + offset + 1)
+            });
+
+            let skip = if e > 0 { ends[e - 1][i] } else { None };
+
+            let (end, typo) = match (found, skip) {
+                (Some(found), Some(skip)) if skip < found => (Some(skip), true),
+                (Some(found), _) => (Some(found), false),
+                (None, skip) => (skip, skip.is_some())
+            };
+
+            ends[e][i + 1] = end;
+            skipped[e][i + 1] = typo;
+        }
+
+        if ends[e][length].is_some() {
+            let mut found = Vec::with_capacity(length - e);
+            let mut e = e;
+
+            for i in (1..(length + 1)).rev() {
+                if skipped[e][i] {
+                    e -= 1;
+                } else {
+                    found.push(i - 1);
+                }
+            }
+
+            found.reverse();
+            return Some(found);
+        }
+    }
+
+    None
+}
+
 /// Compare two `char` for case-insensitive equality.
 #[inline(always)]
 pub fn eq(a: char, b: char) -> bool {
@@ -26,6 +163,28 @@ pub fn eq(a: char, b: char) -> bool {
     }
 }
 
+// Each byte with ASCII letters lowercased
+static LOWERCASE: [u8; 256] = lowercase_table();
+
+const fn lowercase_table() -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        table[i] = (i as u8).to_ascii_lowercase();
+        i += 1;
+    }
+
+    table
+}
+
+/// Compare two ASCII bytes for case-insensitive equality, as `eq` does
+/// their chars.
+#[inline(always)]
+pub fn eq_ascii(a: u8, b: u8) -> bool {
+    LOWERCASE[a as usize] == LOWERCASE[b as usize]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -37,6 +196,15 @@ mod tests {
         assert!(!eq('a', 'b'));
     }
 
+    #[test]
+    fn test_eq_ascii() {
+        for a in 0..128u8 {
+            for b in 0..128u8 {
+                assert_eq!(eq_ascii(a, b), eq(a as char, b as char));
+            }
+        }
+    }
+
     #[test]
     fn test_matches() {
         assert!(matches("", "a"));
@@ -64,5 +232,66 @@ mod tests {
         assert!(matches("café", "CAFÉ"));
         assert!(matches("weiß", "WEIẞ"));
         assert!(matches("хди́ь", "ХОДИ́ТЬ"));
+
+        // ASCII needles in non-ASCII haystacks
+        assert!(matches("ab", "\u{e9}a\u{e9}B"));
+        assert!(!matches("k", "\u{212a}"));
+    }
+
+    #[test]
+    fn test_matches_exact() {
+        assert!(matches_exact("", ""));
+        assert!(matches_exact("", "a"));
+        assert!(matches_exact("abc", "abc"));
+        assert!(matches_exact("bc", "abcd"));
+        assert!(matches_exact("BC", "abcd"));
+        assert!(matches_exact("ab", "aab"));
+        assert!(matches_exact("💣e", "t💣e💣s💣t"));
+        assert!(matches_exact("café", "LE CAFÉ"));
+
+        assert!(!matches_exact("abc", "a1b2c3"));
+        assert!(!matches_exact("abc", "ab"));
+        assert!(!matches_exact("abc", ""));
+    }
+
+    #[test]
+    fn test_matches_prefix() {
+        assert!(matches_prefix("", ""));
+        assert!(matches_prefix("", "a"));
+        assert!(matches_prefix("ab", "abc"));
+        assert!(matches_prefix("AB", "abc"));
+        assert!(matches_prefix("山", "山田"));
+
+        assert!(!matches_prefix("bc", "abc"));
+        assert!(!matches_prefix("abcd", "abc"));
+        assert!(!matches_prefix("ab", "a\u{e9}"));
+    }
+
+    #[test]
+    fn test_matches_with_typos() {
+        assert_eq!(matches_with_typos("", "abc"), Some(vec![]));
+        assert_eq!(matches_with_typos("amor", "app/models/order"), Some(vec![0, 1, 2, 3]));
+
+        // transposition
+        assert_eq!(matches_with_typos("ordre", "order.rb"), Some(vec![0, 1, 2, 4]));
+
+        // substitution
+        assert_eq!(matches_with_typos("ordex", "order.rb"), Some(vec![0, 1, 2, 3]));
+
+        // omission
+        assert_eq!(matches_with_typos("gemfilr", "Gemfile"), Some(vec![0, 1, 2, 3, 4, 5]));
+
+        // too many typos for the needle's length
+        assert_eq!(matches_with_typos("abc", "abd"), None);
+        assert_eq!(matches_with_typos("ordxx", "order.rb"), None);
+        assert_eq!(matches_with_typos("ordre", ""), None);
+    }
+
+    #[test]
+    fn test_occurrences() {
+        assert_eq!(occurrences("a", "banana").collect::<Vec<_>>(), vec![1, 3, 5]);
+        assert_eq!(occurrences("ana", "banana").collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(occurrences("s", "t💣e💣s💣t").collect::<Vec<_>>(), vec![4]);
+        assert_eq!(occurrences("x", "banana").count(), 0);
     }
 }
@@ -4,7 +4,7 @@ use std::ops::{Index, IndexMut};
 type Idx = (usize, usize);
 
 /// A 2-dimensional matrix of f64 values.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Matrix {
     width: usize,
     contents: Vec<f64>,
@@ -18,6 +18,18 @@ impl Matrix {
             contents: vec![0.0; width * height],
         }
     }
+
+    /// Resizes the Matrix to the provided width and height, reusing its
+    /// storage. Values are left as they were in the storage, not cleared.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.contents.resize(width * height, 0.0);
+    }
+
+    /// The number of values the Matrix can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.contents.capacity()
+    }
 }
 
 impl Index<Idx> for Matrix {
@@ -50,4 +62,16 @@ mod test {
         assert_eq!(matrix[(12, 24)], 123.456);
         assert_eq!(matrix[(24, 12)], 0.0);
     }
+
+    #[test]
+    fn test_resize() {
+        let mut matrix = Matrix::new(2, 2);
+        matrix.resize(3, 4);
+
+        matrix[(2, 3)] = 1.0;
+        assert_eq!(matrix[(2, 3)], 1.0);
+
+        matrix.resize(1, 1);
+        assert_eq!(matrix.contents.len(), 1);
+    }
 }
@@ -0,0 +1,91 @@
+//! Read-only memory maps of files, so that large inputs can be searched
+//! without copying them into memory first.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::ptr::{self, NonNull};
+use std::slice;
+
+use libc::{mmap, munmap, MAP_FAILED, MAP_PRIVATE, PROT_READ};
+
+/// The contents of a file, mapped read-only into memory.
+pub struct Mmap {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+// the mapping is never written to, so can be read from any thread
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    /// Maps the whole of `file`, which must be a regular file.
+    ///
+    /// # Safety
+    ///
+    /// The file mustn't be modified or truncated while it's mapped, by this
+    /// process or any other: its mapped contents would change underneath any
+    /// references to them, and reading past a truncated end raises SIGBUS.
+    pub unsafe fn map(file: &File) -> io::Result<Mmap> {
+        let len = usize::try_from(file.metadata()?.len()).
+            map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file too large to map"))?;
+
+        // mapping nothing is an error, but there's nothing to map anyway
+        if len == 0 {
+            return Ok(Mmap { ptr: NonNull::dangling(), len });
+        }
+
+        let ptr = mmap(ptr::null_mut(), len, PROT_READ, MAP_PRIVATE, file.as_raw_fd(), 0);
+
+        if ptr == MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Mmap { ptr: NonNull::new(ptr as *mut u8).unwrap(), len })
+    }
+
+    /// The mapped contents.
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe { munmap(self.ptr.as_ptr() as *mut _, self.len); }
+        }
+    }
+}
+
+impl fmt::Debug for Mmap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Mmap").field("len", &self.len).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_map() {
+        let path = env::temp_dir().join(format!("rff-mmap-{}", std::process::id()));
+
+        fs::write(&path, "app/models\norder.rb\n").unwrap();
+        let map = unsafe { Mmap::map(&File::open(&path).unwrap()).unwrap() };
+        assert_eq!(map.as_bytes(), b"app/models\norder.rb\n");
+        drop(map);
+
+        fs::write(&path, "").unwrap();
+        let map = unsafe { Mmap::map(&File::open(&path).unwrap()).unwrap() };
+        assert_eq!(map.as_bytes(), b"");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,345 @@
+use regex::{self, Regex, RegexBuilder};
+
+use super::{Match, MatchWithPositions};
+use normalize::{is_normalized, normalize, normalize_with_origins};
+use prefilter::Signature;
+use {matcher, scorer, simd};
+
+/// How a search term is matched against each line.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Mode {
+    /// The term's chars appear in order, anywhere in the line
+    #[default]
+    Fuzzy,
+    /// The term appears as a contiguous substring of the line
+    Exact,
+    /// The line begins with the term
+    Prefix,
+    /// The term is a regular expression found somewhere in the line
+    Regex,
+    /// Like `Fuzzy`, but tolerating a few misspelled or missing chars
+    Typos,
+}
+
+impl Mode {
+    /// Whether appending to a search term can only ever remove matches, so
+    /// the new term need only be checked against the previous term's matches.
+    pub fn narrows(&self) -> bool {
+        match *self {
+            // the number of typos tolerated grows with the term
+            Mode::Regex | Mode::Typos => false,
+            _ => true
+        }
+    }
+}
+
+/// A search term, prepared for matching in a particular `Mode`.
+///
+/// Both the term and the lines it's matched against are Unicode normalized
+/// (see the `normalize` module), with diacritics folded away if `fold` is set.
+#[derive(Clone, Debug)]
+pub struct Query {
+    needle: String,
+    mode: Mode,
+    fold: bool,
+    regex: Option<Regex>,
+    // set if every char of the needle must be found in a matching line
+    signature: Option<Signature>,
+}
+
+impl Query {
+    /// Creates a new Query for `needle`, which fails only if `mode` is
+    /// `Mode::Regex` and `needle` is not a valid regular expression.
+    pub fn new(mode: Mode, needle: &str, fold: bool) -> Result<Query, regex::Error> {
+        let needle = normalize(needle, fold).into_owned();
+
+        let regex = match mode {
+            Mode::Regex => Some(RegexBuilder::new(&needle).case_insensitive(true).build()?),
+            _ => None
+        };
+
+        let signature = match mode {
+            Mode::Fuzzy | Mode::Exact | Mode::Prefix => Some(Signature::of(&needle)),
+            Mode::Regex | Mode::Typos => None
+        };
+
+        Ok(Query {
+            needle,
+            mode,
+            fold,
+            regex,
+            signature,
+        })
+    }
+
+    /// Checks whether a line with the signature `line` could match this
+    /// query, as a quick test before calling `matches`.
+    #[inline]
+    pub fn may_match(&self, line: Signature) -> bool {
+        self.signature.is_none_or(|needle| line.may_contain(needle))
+    }
+
+    /// Checks whether `haystack` matches this query.
+    #[inline]
+    pub fn matches(&self, haystack: &str) -> bool {
+        let needle = &self.needle;
+        let haystack = &*normalize(haystack, self.fold);
+
+        match self.regex {
+            Some(ref regex) => regex.is_match(haystack),
+            None => match self.mode {
+                Mode::Exact => matcher::matches_exact(needle, haystack),
+                Mode::Prefix => matcher::matches_prefix(needle, haystack),
+                Mode::Typos => {
+                    simd::matches(needle, haystack) ||
+                        matcher::matches_with_typos(needle, haystack).is_some()
+                },
+                _ => simd::matches(needle, haystack),
+            }
+        }
+    }
+
+    /// Scores `haystack`, which must already match this query.
+    #[inline]
+    pub fn score(&self, haystack: &str) -> f64 {
+        let haystack = &*normalize(haystack, self.fold);
+
+        match self.mode {
+            Mode::Fuzzy => scorer::score(&self.needle, haystack),
+            _ => self.score_normalized(haystack).0
+        }
+    }
+
+    /// Scores `haystack`, which must already match this query, along with the
+    /// positions of the matched chars.
+    #[inline]
+    pub fn score_with_positions(&self, haystack: &str) -> (f64, Vec<usize>) {
+        self.with_normalized(haystack, |normalized| Some(self.score_normalized(normalized))).
+            unwrap()
+    }
+
+    // Applies `f` to `haystack` normalized, mapping the positions it returns
+    // back to the chars of `haystack`
+    fn with_normalized<F>(&self, haystack: &str, f: F) -> Option<(f64, Vec<usize>)>
+        where F: FnOnce(&str) -> Option<(f64, Vec<usize>)>
+    {
+        if is_normalized(haystack, self.fold) {
+            return f(haystack);
+        }
+
+        // positions must refer to the chars of the original haystack
+        let (normalized, origins) = normalize_with_origins(haystack, self.fold);
+        let (score, positions) = f(&normalized)?;
+
+        // combining marks are reordered when normalized, so their origins
+        // can be out of order
+        let mut positions: Vec<usize> = positions.iter().map(|&i| origins[i]).collect();
+        positions.sort_unstable();
+        positions.dedup();
+
+        Some((score, positions))
+    }
+
+    // Matches and scores an already-normalized `haystack` with typos, scoring
+    // the chars the match found rather than searching for them again
+    fn match_typos_normalized(&self, haystack: &str) -> Option<(f64, Vec<usize>)> {
+        let needle = &self.needle;
+
+        if simd::matches(needle, haystack) {
+            return Some(scorer::score_with_positions(needle, haystack));
+        }
+
+        matcher::matches_with_typos(needle, haystack).
+            map(|found| scorer::score_found_with_typos(needle, haystack, &found))
+    }
+
+    // Whether lines are matched and scored in one step, see
+    // `match_typos_normalized`
+    fn is_typos(&self) -> bool {
+        self.mode == Mode::Typos && self.regex.is_none()
+    }
+
+    // Scores an already-normalized `haystack`
+    fn score_normalized(&self, haystack: &str) -> (f64, Vec<usize>) {
+        let needle = &self.needle;
+
+        match self.regex {
+            Some(ref regex) => {
+                // an empty match scores nothing, so prefer any later one
+                // that isn't
+                let found = regex.find_iter(haystack).
+                    find(|found| !found.as_str().is_empty()).
+                    or_else(|| regex.find(haystack));
+
+                match found {
+                    Some(found) => {
+                        let start = haystack[..found.start()].chars().count();
+                        let length = found.as_str().chars().count();
+                        scorer::score_span_with_positions(haystack, start, length)
+                    },
+                    None => scorer::score_span_with_positions(haystack, 0, 0)
+                }
+            },
+            None => match self.mode {
+                Mode::Exact => scorer::score_exact_with_positions(needle, haystack),
+                Mode::Prefix => scorer::score_prefix_with_positions(needle, haystack),
+                Mode::Typos => scorer::score_with_typos(needle, haystack),
+                _ => scorer::score_with_positions(needle, haystack),
+            }
+        }
+    }
+
+    pub fn match_and_score<'a>(&self, haystack: &'a str) -> Option<Match<'a>> {
+        if self.is_typos() {
+            return self.match_and_score_with_positions(haystack).
+                map(|(haystack, score, _)| (haystack, score));
+        }
+
+        if self.matches(haystack) {
+            Some((haystack, self.score(haystack)))
+        } else {
+            None
+        }
+    }
+
+    pub fn match_and_score_with_positions<'a>(&self, haystack: &'a str) -> Option<MatchWithPositions<'a>> {
+        if self.is_typos() {
+            return self.with_normalized(haystack, |normalized| self.match_typos_normalized(normalized)).
+                map(|(score, positions)| (haystack, score, positions));
+        }
+
+        if self.matches(haystack) {
+            let (score, positions) = self.score_with_positions(haystack);
+            Some((haystack, score, positions))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(mode: Mode, needle: &str) -> Query {
+        Query::new(mode, needle, false).unwrap()
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(query(Mode::Fuzzy, "amo").matches("app/models/order"));
+        assert!(!query(Mode::Exact, "amo").matches("app/models/order"));
+        assert!(query(Mode::Exact, "models").matches("app/models/order"));
+        assert!(!query(Mode::Prefix, "models").matches("app/models/order"));
+        assert!(query(Mode::Prefix, "app").matches("app/models/order"));
+        assert!(query(Mode::Regex, "m.d").matches("app/models/order"));
+        assert!(query(Mode::Regex, "^APP/").matches("app/models/order"));
+        assert!(!query(Mode::Regex, "order$").matches("app/models/order.rb"));
+        assert!(query(Mode::Typos, "ordre").matches("app/models/order.rb"));
+        assert!(!query(Mode::Fuzzy, "ordre").matches("app/models/order.rb"));
+    }
+
+    #[test]
+    fn test_may_match() {
+        let line = Signature::of("app/models/order");
+        assert!(query(Mode::Fuzzy, "amo").may_match(line));
+        assert!(!query(Mode::Fuzzy, "amx").may_match(line));
+        assert!(!query(Mode::Exact, "amx").may_match(line));
+
+        // missing chars may be typos, and regexes needn't contain any
+        assert!(query(Mode::Typos, "amx").may_match(line));
+        assert!(query(Mode::Regex, "x?").may_match(line));
+
+        // folded needles are compared once folded
+        let folded = Query::new(Mode::Fuzzy, "\u{e9}", true).unwrap();
+        assert!(folded.may_match(Signature::of("e")));
+    }
+
+    #[test]
+    fn test_narrows() {
+        assert!(Mode::Fuzzy.narrows());
+        assert!(Mode::Exact.narrows());
+        assert!(Mode::Prefix.narrows());
+        assert!(!Mode::Regex.narrows());
+        assert!(!Mode::Typos.narrows());
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        assert!(Query::new(Mode::Regex, "models/(", false).is_err());
+        assert!(Query::new(Mode::Fuzzy, "models/(", false).is_ok());
+    }
+
+    #[test]
+    fn test_normalization() {
+        // NFC and NFD forms match each other
+        assert!(query(Mode::Exact, "caf\u{e9}").matches("cafe\u{301}.txt"));
+        assert!(query(Mode::Exact, "cafe\u{301}").matches("caf\u{e9}.txt"));
+        assert!(!query(Mode::Exact, "cafe").matches("caf\u{e9}.txt"));
+
+        let folded = Query::new(Mode::Exact, "cafe", true).unwrap();
+        assert!(folded.matches("caf\u{e9}.txt"));
+        assert!(folded.matches("CAFE\u{301}.txt"));
+
+        let folded = Query::new(Mode::Fuzzy, "caf\u{e9}", true).unwrap();
+        assert!(folded.matches("cafe.txt"));
+    }
+
+    #[test]
+    fn test_normalized_positions() {
+        let (_, positions) = query(Mode::Fuzzy, "\u{e9}t").score_with_positions("e\u{301}te\u{301}");
+        assert_eq!(positions, vec![0, 2]);
+
+        let folded = Query::new(Mode::Exact, "ete", true).unwrap();
+        let (_, positions) = folded.score_with_positions("l'\u{e9}t\u{e9}");
+        assert_eq!(positions, vec![2, 3, 4]);
+
+        // jamo compose with each other, not just with combining marks
+        let hangul = query(Mode::Exact, "\u{d55c}\u{ae00}");
+        let line = "\u{1112}\u{1161}\u{11ab}\u{1100}\u{1173}\u{11af}.txt";
+        assert!(hangul.matches(line));
+        assert_eq!(hangul.score_with_positions(line).1, vec![0, 3]);
+    }
+
+    #[test]
+    fn test_match_and_score_with_positions() {
+        let (_, _, positions) = query(Mode::Exact, "mod").
+            match_and_score_with_positions("app/models/order").
+            unwrap();
+
+        assert_eq!(positions, vec![4, 5, 6]);
+        assert!(query(Mode::Prefix, "mod").match_and_score_with_positions("app/models/order").is_none());
+
+        let (_, _, positions) = query(Mode::Regex, "o.d").
+            match_and_score_with_positions("café/models/order").
+            unwrap();
+
+        assert_eq!(positions, vec![12, 13, 14]);
+    }
+
+    #[test]
+    fn test_match_and_score_typos() {
+        let typos = query(Mode::Typos, "ordre");
+
+        for line in &["app/models/order.rb", "app/models/ordre.rb", "\u{d6}rder.rb", "spec"] {
+            let expected = if typos.matches(line) {
+                let (score, positions) = typos.score_with_positions(line);
+                Some((*line, score, positions))
+            } else {
+                None
+            };
+
+            assert_eq!(typos.match_and_score_with_positions(line), expected);
+            assert_eq!(typos.match_and_score(line), expected.map(|(line, score, _)| (line, score)));
+        }
+    }
+
+    #[test]
+    fn test_regex_empty_matches() {
+        let regex = query(Mode::Regex, "x*");
+
+        assert_eq!(regex.score_with_positions("axx").1, vec![1, 2]);
+        assert!(regex.score("axx") > regex.score("b"));
+        assert_eq!(regex.score_with_positions("b").1, vec![]);
+    }
+}
@@ -0,0 +1,183 @@
+//! Unicode normalization of search terms and lines.
+//!
+//! Lines are compared in NFC, so that e.g. filenames decomposed into NFD (as
+//! created on macOS) match the precomposed form a user types. Optionally,
+//! diacritics can be folded away entirely, so that `cafe` matches `café`.
+
+use std::borrow::Cow;
+
+use unicode_normalization::{is_nfc_quick, is_nfd_quick, IsNormalized, UnicodeNormalization};
+use unicode_normalization::char::{canonical_combining_class, compose, decompose_canonical, is_combining_mark};
+
+/// Checks whether `s` is unaffected by `normalize`.
+#[inline]
+pub fn is_normalized(s: &str, fold: bool) -> bool {
+    if s.is_ascii() {
+        return true;
+    }
+
+    if fold {
+        is_nfd_quick(s.chars()) == IsNormalized::Yes && !s.chars().any(is_combining_mark)
+    } else {
+        is_nfc_quick(s.chars()) == IsNormalized::Yes
+    }
+}
+
+/// Normalizes `s` to NFC or, when folding, to NFD with all combining marks
+/// removed.
+///
+/// # Examples
+///
+/// ```
+/// use rff::normalize::normalize;
+///
+/// assert_eq!(normalize("cafe\u{301}", false), "caf\u{e9}");
+/// assert_eq!(normalize("caf\u{e9}", true), "cafe");
+/// ```
+pub fn normalize(s: &str, fold: bool) -> Cow<'_, str> {
+    if is_normalized(s, fold) {
+        Cow::Borrowed(s)
+    } else if fold {
+        Cow::Owned(s.nfd().filter(|&ch| !is_combining_mark(ch)).collect())
+    } else {
+        Cow::Owned(s.nfc().collect())
+    }
+}
+
+/// Normalizes `s` as `normalize` does, along with the index of the char in
+/// `s` that each normalized char came from.
+///
+/// The whole of `s` is decomposed, reordered and, unless folding, composed
+/// again, following each char from its origin. Composed chars map back to
+/// the starter they were composed onto.
+pub fn normalize_with_origins(s: &str, fold: bool) -> (String, Vec<usize>) {
+    let mut chars: Vec<(char, usize)> = Vec::with_capacity(s.len());
+
+    for (index, ch) in s.chars().enumerate() {
+        decompose_canonical(ch, |decomposed| chars.push((decomposed, index)));
+    }
+
+    // combining marks are put in canonical order, keeping the order of
+    // those in the same class
+    let mut start = 0;
+
+    while start < chars.len() {
+        let end = chars[start..].iter().
+            position(|&(ch, _)| canonical_combining_class(ch) == 0).
+            map_or(chars.len(), |length| start + length);
+
+        chars[start..end].sort_by_key(|&(ch, _)| canonical_combining_class(ch));
+        start = end + 1;
+    }
+
+    if fold {
+        chars.retain(|&(ch, _)| !is_combining_mark(ch));
+    } else {
+        chars = compose_with_origins(chars);
+    }
+
+    chars.into_iter().unzip()
+}
+
+// Composes canonically decomposed chars as NFC does, each composed char
+// taking the origin of its starter
+fn compose_with_origins(chars: Vec<(char, usize)>) -> Vec<(char, usize)> {
+    let mut composed: Vec<(char, usize)> = Vec::with_capacity(chars.len());
+    // the last starter composed, and the class of the last char after it
+    let mut starter: Option<usize> = None;
+    let mut last_class = 0;
+
+    for (ch, origin) in chars {
+        let class = canonical_combining_class(ch);
+
+        if let Some(starter) = starter {
+            // chars are blocked from the starter by those between them of
+            // the same or a higher class, or by any starter
+            let blocked = composed.len() > starter + 1 && last_class >= class;
+
+            if !blocked {
+                if let Some(ch) = compose(composed[starter].0, ch) {
+                    composed[starter].0 = ch;
+                    continue;
+                }
+            }
+        }
+
+        if class == 0 {
+            starter = Some(composed.len());
+        }
+
+        last_class = class;
+        composed.push((ch, origin));
+    }
+
+    composed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_normalized() {
+        assert!(is_normalized("app/models/order.rb", false));
+        assert!(is_normalized("app/models/order.rb", true));
+        assert!(is_normalized("caf\u{e9}", false));
+        assert!(!is_normalized("caf\u{e9}", true));
+        assert!(!is_normalized("cafe\u{301}", false));
+        assert!(!is_normalized("cafe\u{301}", true));
+        assert!(is_normalized("山田", true));
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("cafe\u{301}", false), "caf\u{e9}");
+        assert_eq!(normalize("caf\u{e9}", false), "caf\u{e9}");
+        assert_eq!(normalize("cafe\u{301}", true), "cafe");
+        assert_eq!(normalize("caf\u{e9}", true), "cafe");
+        assert_eq!(normalize("Ångström", true), "Angstrom");
+    }
+
+    #[test]
+    fn test_normalize_with_origins() {
+        assert_eq!(normalize_with_origins("abc", false), (String::from("abc"), vec![0, 1, 2]));
+
+        let (normalized, origins) = normalize_with_origins("e\u{301}te\u{301}", false);
+        assert_eq!(normalized, "\u{e9}t\u{e9}");
+        assert_eq!(origins, vec![0, 2, 3]);
+
+        let (normalized, origins) = normalize_with_origins("\u{e9}t\u{e9}!", true);
+        assert_eq!(normalized, "ete!");
+        assert_eq!(origins, vec![0, 1, 2, 3]);
+
+        // decomposed Hangul, as macOS stores it, composes across starters
+        let (normalized, origins) = normalize_with_origins("\u{1112}\u{1161}\u{11ab}\u{1100}\u{1173}\u{11af}.txt", false);
+        assert_eq!(normalized, "\u{d55c}\u{ae00}.txt");
+        assert_eq!(origins, vec![0, 3, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_normalize_with_origins_matches_normalize() {
+        let cases = [
+            "\u{1112}\u{1161}\u{11ab}\u{1100}\u{1173}\u{11af}",
+            "\u{d55c}\u{11af}",
+            "a\u{301}\u{323}",
+            "a\u{323}\u{301}b\u{308}\u{301}",
+            "\u{212b}ngstro\u{308}m",
+            "\u{301}leading",
+            "e\u{301}\u{301}",
+            "\u{fb2c}\u{5b0}",
+            "\u{f73}\u{f75}\u{f81}",
+        ];
+
+        for s in &cases {
+            for &fold in &[false, true] {
+                let (normalized, origins) = normalize_with_origins(s, fold);
+
+                assert_eq!(normalized, normalize(s, fold), "{:?}", s);
+                assert_eq!(origins.len(), normalized.chars().count());
+                assert!(origins.iter().all(|&origin| origin < s.chars().count()));
+            }
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! A cheap test that rules out most lines that can't match a search term,
+//! before the matcher is ever run on them.
+//!
+//! Each line is summarised by a `Signature`, computed once as lines are read.
+//! Comparing it with the search term's signature is a couple of bitwise
+//! operations, where matching walks the whole line.
+
+// bits 0-25 are the letters a-z, and 26-35 the digits 0-9
+const NON_ASCII: u64 = 1 << 63;
+
+/// The set of ASCII letters and digits in a string, ignoring case, and
+/// whether it contains any non-ASCII chars.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Signature(u64);
+
+impl Signature {
+    /// Computes the signature of `s`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rff::prefilter::Signature;
+    ///
+    /// assert_eq!(Signature::of("App/Models"), Signature::of("adelmops"));
+    /// ```
+    #[inline]
+    pub fn of(s: &str) -> Signature {
+        let mut bits = 0;
+
+        for &b in s.as_bytes() {
+            bits |= match b {
+                b'a'..=b'z' => 1 << (b - b'a'),
+                b'A'..=b'Z' => 1 << (b - b'A'),
+                b'0'..=b'9' => 1 << (26 + b - b'0'),
+                0x80..=0xff => NON_ASCII,
+                _ => 0
+            };
+        }
+
+        Signature(bits)
+    }
+
+    /// Whether a line with this signature could contain every char of a
+    /// needle with the signature `needle`, as the matcher compares them.
+    ///
+    /// ASCII chars only ever equal ASCII chars, ignoring case, so an ASCII
+    /// line can be ruled out if it lacks any of the needle's letters or
+    /// digits, or the needle has any non-ASCII chars. A line with non-ASCII
+    /// chars may gain ASCII ones when normalized, so is never ruled out.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rff::prefilter::Signature;
+    ///
+    /// let line = Signature::of("app/models/order.rb");
+    /// assert!(line.may_contain(Signature::of("amo")));
+    /// assert!(!line.may_contain(Signature::of("amx")));
+    /// ```
+    #[inline]
+    pub fn may_contain(self, needle: Signature) -> bool {
+        self.0 & NON_ASCII != 0 || needle.0 & !self.0 == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_of() {
+        assert_eq!(Signature::of(""), Signature(0));
+        assert_eq!(Signature::of("aA"), Signature(1));
+        assert_eq!(Signature::of("z9"), Signature(1 << 25 | 1 << 35));
+        assert_eq!(Signature::of("./-_ "), Signature(0));
+        assert_eq!(Signature::of("é"), Signature(NON_ASCII));
+    }
+
+    #[test]
+    fn test_may_contain() {
+        let line = Signature::of("src/main.rs");
+
+        assert!(line.may_contain(Signature::of("")));
+        assert!(line.may_contain(Signature::of("MAIN")));
+        assert!(line.may_contain(Signature::of("sm/.")));
+        assert!(!line.may_contain(Signature::of("main2")));
+        assert!(!line.may_contain(Signature::of("mäin")));
+    }
+
+    #[test]
+    fn test_may_contain_non_ascii() {
+        // normalized, the Kelvin sign is an ASCII K
+        let line = Signature::of("\u{212a}elvin");
+        assert!(line.may_contain(Signature::of("kelvin")));
+        assert!(line.may_contain(Signature::of("xyz")));
+    }
+}
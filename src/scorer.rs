@@ -3,10 +3,30 @@
 // Licensed under the MIT license
 // https://github.com/jhawthorn/fzy
 
+use std::cell::RefCell;
+
 use consts::*;
-use matcher::eq;
+use matcher::{eq, eq_ascii, occurrences, matches_with_typos};
 use matrix::Matrix;
 
+// Storage reused from one line scored to the next, so that scoring a line
+// doesn't allocate
+#[derive(Default)]
+struct Buffers {
+    bonus: Vec<f64>,
+    d: Matrix,
+    m: Matrix,
+}
+
+thread_local! {
+    static BUFFERS: RefCell<Buffers> = RefCell::new(Buffers::default());
+}
+
+// The most cells the reused matrices are grown to. Larger ones, for long
+// needles scored with positions, are allocated for the one line, so that each
+// thread doesn't keep up to 1024 x 1024 cells around for good.
+const MAX_BUFFERED: usize = 64 << 10;
+
 pub fn score(needle: &str, haystack: &str) -> f64 {
     let needle_length = needle.chars().count();
 
@@ -27,9 +47,11 @@ pub fn score(needle: &str, haystack: &str) -> f64 {
         return SCORE_MIN;
     }
 
-    let (_, m) = calculate_score(needle, needle_length, haystack, haystack_length);
+    with_buffers(2 * haystack_length, |buffers| {
+        calculate_score(buffers, needle, needle_length, haystack, haystack_length, true);
 
-    m[(needle_length - 1, haystack_length - 1)]
+        buffers.m[((needle_length - 1) % 2, haystack_length - 1)]
+    })
 }
 
 pub fn score_with_positions(needle: &str, haystack: &str) -> (f64, Vec<usize>) {
@@ -52,7 +74,15 @@ pub fn score_with_positions(needle: &str, haystack: &str) -> (f64, Vec<usize>) {
         return (SCORE_MIN, vec![]);
     }
 
-    let (d, m) = calculate_score(needle, needle_length, haystack, haystack_length);
+    with_buffers(needle_length * haystack_length, |buffers| {
+        calculate_score(buffers, needle, needle_length, haystack, haystack_length, false);
+
+        backtrack(&buffers.d, &buffers.m, needle_length, haystack_length)
+    })
+}
+
+// Finds the positions of the best match in the d and m matrices
+fn backtrack(d: &Matrix, m: &Matrix, needle_length: usize, haystack_length: usize) -> (f64, Vec<usize>) {
     let mut positions = vec![0 as usize; needle_length];
 
     {
@@ -84,25 +114,190 @@ pub fn score_with_positions(needle: &str, haystack: &str) -> (f64, Vec<usize>) {
     (m[(needle_length - 1, haystack_length - 1)], positions)
 }
 
-fn calculate_score(needle: &str, needle_length: usize, haystack: &str, haystack_length: usize) -> (Matrix, Matrix) {
-    let bonus = compute_bonus(haystack);
+/// Scores needle against a haystack it matches with typos, as found by
+/// `matcher::matches_with_typos`.
+///
+/// The needle chars that were found are scored as usual, and their positions
+/// returned, with `SCORE_TYPO` applied for each one that was not.
+pub fn score_with_typos(needle: &str, haystack: &str) -> (f64, Vec<usize>) {
+    match matches_with_typos(needle, haystack) {
+        Some(found) => score_found_with_typos(needle, haystack, &found),
+        None => (SCORE_MIN, vec![])
+    }
+}
+
+/// Scores needle as `score_with_typos` does, given the indices of the needle
+/// chars that `matcher::matches_with_typos` found in haystack.
+pub fn score_found_with_typos(needle: &str, haystack: &str, found: &[usize]) -> (f64, Vec<usize>) {
+    let typos = needle.chars().count() - found.len();
+
+    if typos == 0 {
+        return score_with_positions(needle, haystack);
+    }
+
+    let found_needle: String = needle.chars().
+        enumerate().
+        filter(|&(i, _)| found.contains(&i)).
+        map(|(_, ch)| ch).
+        collect();
+
+    let (score, positions) = score_with_positions(&found_needle, haystack);
+
+    // a needle with typos is never a perfect match
+    let score = if score == SCORE_MAX { 0.0 } else { score };
+
+    (score + (typos as f64) * SCORE_TYPO, positions)
+}
+
+/// Scores needle as a contiguous run of chars in the haystack, using the best
+/// scoring occurrence.
+///
+/// Boundary bonuses apply to the first char of the run just as they do for
+/// fuzzy matches, so an occurrence following a slash is preferred over one in
+/// the middle of a word.
+pub fn score_exact_with_positions(needle: &str, haystack: &str) -> (f64, Vec<usize>) {
+    score_contiguous(needle, haystack, false)
+}
+
+/// Scores needle as a contiguous run of chars at the beginning of haystack.
+pub fn score_prefix_with_positions(needle: &str, haystack: &str) -> (f64, Vec<usize>) {
+    score_contiguous(needle, haystack, true)
+}
+
+fn score_contiguous(needle: &str, haystack: &str, prefix: bool) -> (f64, Vec<usize>) {
+    let needle_length = needle.chars().count();
+
+    // empty needle
+    if needle_length == 0 {
+        return (SCORE_MIN, vec![]);
+    }
+
+    let haystack_length = haystack.chars().count();
+
+    // perfect match
+    if needle_length == haystack_length {
+        return (SCORE_MAX, (0..needle_length).collect());
+    }
+
+    // unreasonably large haystack
+    if haystack_length > 1024 {
+        return (SCORE_MIN, vec![]);
+    }
+
+    let best = with_bonus(haystack, |bonus| {
+        occurrences(needle, haystack).
+            take_while(|&start| !prefix || start == 0).
+            map(|start| (span_score(bonus, start, needle_length), start)).
+            fold(None, |best: Option<(f64, usize)>, (score, start)| {
+                match best {
+                    Some((best_score, _)) if best_score >= score => best,
+                    _ => Some((score, start))
+                }
+            })
+    });
+
+    match best {
+        Some((score, start)) => (score, (start..start + needle_length).collect()),
+        None => (SCORE_MIN, vec![])
+    }
+}
+
+/// Scores the contiguous run of `length` chars starting at char `start` of
+/// haystack, as found by e.g. a regular expression.
+pub fn score_span_with_positions(haystack: &str, start: usize, length: usize) -> (f64, Vec<usize>) {
+    // empty span
+    if length == 0 {
+        return (SCORE_MIN, vec![]);
+    }
+
+    let haystack_length = haystack.chars().count();
+
+    // perfect match
+    if start == 0 && length == haystack_length {
+        return (SCORE_MAX, (0..length).collect());
+    }
+
+    // unreasonably large haystack
+    if haystack_length > 1024 {
+        return (SCORE_MIN, vec![]);
+    }
+
+    let score = with_bonus(haystack, |bonus| span_score(bonus, start, length));
+
+    (score, (start..start + length).collect())
+}
+
+fn span_score(bonus: &[f64], start: usize, length: usize) -> f64 {
+    let mut score = (start as f64) * SCORE_GAP_LEADING + bonus[start];
+
+    for bonus_score in &bonus[(start + 1)..(start + length)] {
+        score += bonus_score.max(SCORE_MATCH_CONSECUTIVE);
+    }
+
+    let trailing = bonus.len() - start - length;
+    score + (trailing as f64) * SCORE_GAP_TRAILING
+}
+
+// Applies `f` to the thread's buffers or, if matrices of `cells` would grow
+// them past `MAX_BUFFERED`, to buffers of its own
+fn with_buffers<T, F: FnOnce(&mut Buffers) -> T>(cells: usize, f: F) -> T {
+    if cells > MAX_BUFFERED {
+        return f(&mut Buffers::default());
+    }
 
-    let mut m = Matrix::new(needle_length, haystack_length);
-    let mut d = Matrix::new(needle_length, haystack_length);
+    BUFFERS.with(|buffers| f(&mut buffers.borrow_mut()))
+}
+
+// Computes the bonuses for haystack, reusing the buffers' storage
+fn with_bonus<T, F: FnOnce(&[f64]) -> T>(haystack: &str, f: F) -> T {
+    BUFFERS.with(|buffers| {
+        let bonus = &mut buffers.borrow_mut().bonus;
+        compute_bonus_into(haystack, bonus);
+
+        f(bonus)
+    })
+}
+
+// Fills in the buffers' d and m matrices for needle and haystack. Rolling,
+// only two rows are kept, so that row `i` is at `i % 2`, which is enough to
+// find the score but not the positions.
+fn calculate_score(buffers: &mut Buffers, needle: &str, needle_length: usize, haystack: &str, haystack_length: usize, rolling: bool) {
+    let rows = if rolling { 2 } else { needle_length };
+
+    compute_bonus_into(haystack, &mut buffers.bonus);
+    buffers.d.resize(rows, haystack_length);
+    buffers.m.resize(rows, haystack_length);
+
+    if needle.is_ascii() && haystack.is_ascii() {
+        fill_matrices(buffers, needle.bytes(), needle_length, haystack.bytes(), eq_ascii, rolling)
+    } else {
+        fill_matrices(buffers, needle.chars(), needle_length, haystack.chars(), eq, rolling)
+    }
+}
+
+// Fills in the matrices for needle and haystack's chars or, when both are
+// ASCII, their bytes
+fn fill_matrices<T, N, H, F>(buffers: &mut Buffers, needle: N, needle_length: usize, haystack: H, eq: F, rolling: bool)
+    where T: Copy, N: Iterator<Item = T>, H: Iterator<Item = T> + Clone, F: Fn(T, T) -> bool
+{
+    let Buffers { ref bonus, ref mut d, ref mut m } = *buffers;
+
+    for (i, n) in needle.enumerate() {
+        let row = if rolling { i % 2 } else { i };
+        let last_row = if rolling { 1 - row } else { i.saturating_sub(1) };
 
-    for (i, n) in needle.chars().enumerate() {
         let mut prev_score = SCORE_MIN;
         let gap_score = if i == needle_length - 1 { SCORE_GAP_TRAILING } else { SCORE_GAP_INNER };
 
-        for (j, h) in haystack.chars().enumerate() {
+        for (j, h) in haystack.clone().enumerate() {
             if eq(n, h) {
                 let bonus_score = bonus[j];
 
                 let score = match i {
                     0 => ((j as f64) * SCORE_GAP_LEADING) + bonus_score,
                     _ if j > 0 => {
-                        let m = m[(i - 1, j - 1)];
-                        let d = d[(i - 1, j - 1)];
+                        let m = m[(last_row, j - 1)];
+                        let d = d[(last_row, j - 1)];
 
                         let m = m + bonus_score;
                         let d = d + SCORE_MATCH_CONSECUTIVE;
@@ -114,34 +309,100 @@ fn calculate_score(needle: &str, needle_length: usize, haystack: &str, haystack_
 
                 prev_score = score.max(prev_score + gap_score);
 
-                d[(i, j)] = score;
-                m[(i, j)] = prev_score;
+                d[(row, j)] = score;
+                m[(row, j)] = prev_score;
             } else {
                 prev_score += gap_score;
 
-                d[(i, j)] = SCORE_MIN;
-                m[(i, j)] = prev_score;
+                d[(row, j)] = SCORE_MIN;
+                m[(row, j)] = prev_score;
             }
         }
     }
-
-    (d, m)
 }
 
 pub fn compute_bonus(haystack: &str) -> Vec<f64> {
-    let mut last_char = '/';
+    let mut bonus = Vec::with_capacity(haystack.len());
+    compute_bonus_into(haystack, &mut bonus);
+    bonus
+}
 
-    let (_, len) = haystack.chars().size_hint();
-    let len = len.unwrap_or_else(|| haystack.chars().count());
+fn compute_bonus_into(haystack: &str, bonus: &mut Vec<f64>) {
+    bonus.clear();
 
-    haystack.chars().fold(Vec::with_capacity(len), |mut vec, ch| {
-        vec.push(bonus_for_char(last_char, ch));
+    if haystack.is_ascii() {
+        return compute_bonus_ascii(haystack.as_bytes(), bonus);
+    }
+
+    let mut last_char = '/';
+
+    bonus.extend(haystack.chars().map(|ch| {
+        let score = bonus_for_char(last_char, ch);
         last_char = ch;
-        vec
-    })
+        score
+    }));
+}
+
+fn compute_bonus_ascii(haystack: &[u8], bonus: &mut Vec<f64>) {
+    let mut last_class = BYTE_CLASSES[b'/' as usize];
+
+    bonus.extend(haystack.iter().map(|&b| {
+        let class = BYTE_CLASSES[b as usize];
+        let score = CLASS_BONUSES[last_class as usize][class as usize];
+
+        last_class = class;
+        score
+    }));
+}
+
+// A char from each class of bytes that bonuses tell apart
+const CLASS_CHARS: [char; 7] = ['*', '/', '-', '.', 'a', '0', 'A'];
+
+// The index in CLASS_CHARS of each byte's class
+static BYTE_CLASSES: [u8; 256] = byte_classes();
+
+// The bonus for a byte by the class of the byte before it, and its own
+static CLASS_BONUSES: [[f64; 7]; 7] = class_bonuses();
+
+const fn byte_classes() -> [u8; 256] {
+    let mut classes = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        classes[i] = match i as u8 {
+            b'/' => 1,
+            b'-' | b'_' | b' ' => 2,
+            b'.' => 3,
+            b'a' ..= b'z' => 4,
+            b'0' ..= b'9' => 5,
+            b'A' ..= b'Z' => 6,
+            _ => 0
+        };
+        i += 1;
+    }
+
+    classes
+}
+
+const fn class_bonuses() -> [[f64; 7]; 7] {
+    let mut bonuses = [[0.0; 7]; 7];
+    let mut prev = 0;
+
+    while prev < 7 {
+        let mut current = 0;
+
+        while current < 7 {
+            bonuses[prev][current] = bonus_for_char(CLASS_CHARS[prev], CLASS_CHARS[current]);
+            current += 1;
+        }
+
+        prev += 1;
+    }
+
+    bonuses
 }
 
-fn bonus_for_char(prev: char, current: char) -> f64 {
+const fn bonus_for_char(prev: char, current: char) -> f64 {
     match current {
         'a' ..= 'z' | '0' ..= '9' => bonus_for_prev(prev),
         'A' ..= 'Z' => {
@@ -154,7 +415,7 @@ fn bonus_for_char(prev: char, current: char) -> f64 {
     }
 }
 
-fn bonus_for_prev(ch: char) -> f64 {
+const fn bonus_for_prev(ch: char) -> f64 {
     match ch {
         '/' => SCORE_MATCH_SLASH,
         '-' | '_' | ' ' => SCORE_MATCH_WORD,
@@ -205,15 +466,58 @@ mod tests {
         assert!(score("abc", " a b c    ") > score("abc", " a  b  c "));
     }
 
+    #[test]
+    fn rolling_scores() {
+        let cases = [
+            ("amor", "app/models/order"),
+            ("a", "xyz/abc"),
+            ("abc", "a_b_c_abc"),
+            ("ß", "öäßéè"),
+            ("gemfil", "Gemfile.lock"),
+        ];
+
+        for &(needle, haystack) in &cases {
+            assert_eq!(score(needle, haystack), score_with_positions(needle, haystack).0);
+        }
+    }
+
+    #[test]
+    fn buffers_bounded() {
+        let needle = "ab".repeat(300);
+        let haystack = format!("x{}", needle);
+        let (_, positions) = score_with_positions(&needle, &haystack);
+        assert_eq!(positions, (1..haystack.len()).collect::<Vec<_>>());
+
+        BUFFERS.with(|buffers| {
+            let buffers = buffers.borrow();
+            assert!(buffers.d.capacity() <= MAX_BUFFERED);
+            assert!(buffers.m.capacity() <= MAX_BUFFERED);
+        });
+    }
+
     #[test]
     fn score_utf8() {
         assert_eq!(score("ß", "öäßéè"), -0.02);
+        assert_eq!(score("amor", "app/models/örder"), score("amor", "app/models/xrder"));
     }
 
     #[test]
     fn test_compute_bonus() {
         assert_eq!(compute_bonus("a/b/c/d"), vec![0.9, 0.0, 0.9, 0.0, 0.9, 0.0, 0.9]);
         assert_eq!(compute_bonus("aTestString"), vec![0.9, 0.7, 0.0, 0.0, 0.0, 0.7, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(compute_bonus("\u{e9}a.b"), vec![0.0, 0.0, 0.0, 0.6]);
+    }
+
+    #[test]
+    fn test_compute_bonus_ascii() {
+        for prev in 0..128u8 {
+            for current in 0..128u8 {
+                let expected = bonus_for_char(prev as char, current as char);
+                let mut bonus = vec![];
+                compute_bonus_ascii(&[prev, current], &mut bonus);
+                assert_eq!(bonus[1], expected);
+            }
+        }
     }
 
     #[test]
@@ -244,6 +548,53 @@ mod tests {
         assert_eq!(bonus_for_char('a', 'A'), SCORE_MATCH_CAPITAL);
     }
 
+    #[test]
+    fn exact_scores() {
+        let (score, positions) = score_exact_with_positions("order", "app/models/order.rb");
+        assert_eq!(positions, vec![11, 12, 13, 14, 15]);
+        assert!(score > score_exact_with_positions("order", "app/models/reorder.rb").0);
+
+        // prefers the occurrence on a boundary
+        let (_, positions) = score_exact_with_positions("ab", "xab/ab");
+        assert_eq!(positions, vec![4, 5]);
+
+        assert_eq!(score_exact_with_positions("", "asdf"), (SCORE_MIN, vec![]));
+        assert_eq!(score_exact_with_positions("asdf", "ASDF"), (SCORE_MAX, vec![0, 1, 2, 3]));
+        assert_eq!(score_exact_with_positions("abc", "a/b/c").0, SCORE_MIN);
+    }
+
+    #[test]
+    fn prefix_scores() {
+        let (score, positions) = score_prefix_with_positions("app", "app/models/order.rb");
+        assert_eq!(positions, vec![0, 1, 2]);
+        assert!(score > score_prefix_with_positions("app", "app/models/order/line_item.rb").0);
+
+        assert_eq!(score_prefix_with_positions("ab", "xab/ab"), (SCORE_MIN, vec![]));
+    }
+
+    #[test]
+    fn typo_scores() {
+        let (typo_score, positions) = score_with_typos("ordre", "order.rb");
+        assert_eq!(positions, vec![0, 1, 2, 3]);
+        assert_eq!(typo_score, score("orde", "order.rb") + SCORE_TYPO);
+
+        // without typos, scores are unchanged
+        assert_eq!(score_with_typos("amor", "app/models/order"), score_with_positions("amor", "app/models/order"));
+
+        assert_eq!(score_with_typos("abcx", "abc").0, SCORE_TYPO);
+        assert_eq!(score_with_typos("abc", "xyz"), (SCORE_MIN, vec![]));
+    }
+
+    #[test]
+    fn span_scores() {
+        let (score, positions) = score_span_with_positions("app/models/order.rb", 11, 5);
+        assert_eq!(positions, vec![11, 12, 13, 14, 15]);
+        assert_eq!(score, score_exact_with_positions("order", "app/models/order.rb").0);
+
+        assert_eq!(score_span_with_positions("abc", 0, 0), (SCORE_MIN, vec![]));
+        assert_eq!(score_span_with_positions("abc", 0, 3), (SCORE_MAX, vec![0, 1, 2]));
+    }
+
     #[test]
     fn positions() {
         macro_rules! test_positions {
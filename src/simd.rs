@@ -0,0 +1,241 @@
+//! Vectorized fuzzy matching of ASCII search terms.
+//!
+//! Each char of the search term is found by comparing the haystack with it
+//! many bytes at a time: 32 with AVX2 or 16 with SSE2 on x86_64, picked at
+//! runtime. Short haystacks, other architectures and search terms with
+//! non-ASCII chars fall back to comparing one char at a time.
+
+use std::sync::OnceLock;
+
+use matcher::{self, eq_ascii};
+
+// Finds an ASCII byte in a haystack, as `find` does
+type Find = fn(u8, &[u8]) -> Option<usize>;
+
+/// Searches for needle's chars in the haystack, exactly as
+/// `matcher::matches` does.
+///
+/// # Examples
+///
+/// ```
+/// assert!(rff::simd::matches("amo", "app/models/order"));
+/// assert!(!rff::simd::matches("oma", "app/models/order"));
+/// ```
+#[inline]
+pub fn matches(needle: &str, haystack: &str) -> bool {
+    // ASCII bytes never appear within other chars, see `matcher::matches`
+    if !needle.is_ascii() {
+        return matcher::matches(needle, haystack);
+    }
+
+    let find = implementation();
+    let haystack = haystack.as_bytes();
+    let mut start = 0;
+
+    needle.bytes().all(|n| {
+        match find(n, &haystack[start..]) {
+            Some(position) => {
+                start += position + 1;
+                true
+            },
+            None => false
+        }
+    })
+}
+
+/// Finds the first byte of the haystack equal to the ASCII byte `needle`,
+/// ignoring case.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(rff::simd::find(b'm', b"app/Models"), Some(4));
+/// ```
+#[inline]
+pub fn find(needle: u8, haystack: &[u8]) -> Option<usize> {
+    implementation()(needle, haystack)
+}
+
+// The fastest `find` this CPU supports, detected on first use
+fn implementation() -> Find {
+    static FIND: OnceLock<Find> = OnceLock::new();
+    *FIND.get_or_init(detect)
+}
+
+#[cfg(target_arch = "x86_64")]
+fn detect() -> Find {
+    if is_x86_feature_detected!("avx2") {
+        x86::find_with_avx2
+    } else {
+        x86::find_with_sse2
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn detect() -> Find {
+    find_scalar
+}
+
+fn find_scalar(needle: u8, haystack: &[u8]) -> Option<usize> {
+    haystack.iter().position(|&h| eq_ascii(needle, h))
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    pub const SSE2_WIDTH: usize = 16;
+    pub const AVX2_WIDTH: usize = 32;
+
+    // Only chosen once AVX2 is detected. Shorter haystacks are searched with
+    // SSE2, or a char at a time.
+    pub fn find_with_avx2(needle: u8, haystack: &[u8]) -> Option<usize> {
+        if haystack.len() >= AVX2_WIDTH {
+            unsafe { find_avx2(needle, haystack) }
+        } else {
+            find_with_sse2(needle, haystack)
+        }
+    }
+
+    // SSE2 is part of x86_64, so needn't be detected
+    pub fn find_with_sse2(needle: u8, haystack: &[u8]) -> Option<usize> {
+        if haystack.len() >= SSE2_WIDTH {
+            unsafe { find_sse2(needle, haystack) }
+        } else {
+            super::find_scalar(needle, haystack)
+        }
+    }
+
+    // The haystack must be at least SSE2_WIDTH bytes long. Its last chunk
+    // overlaps the one before, whose bytes are already known not to match.
+    #[target_feature(enable = "sse2")]
+    pub unsafe fn find_sse2(needle: u8, haystack: &[u8]) -> Option<usize> {
+        let lower = _mm_set1_epi8(needle.to_ascii_lowercase() as i8);
+        let upper = _mm_set1_epi8(needle.to_ascii_uppercase() as i8);
+
+        let find_in = |offset: usize| {
+            let chunk = _mm_loadu_si128(haystack.as_ptr().add(offset) as *const __m128i);
+            let found = _mm_or_si128(_mm_cmpeq_epi8(chunk, lower), _mm_cmpeq_epi8(chunk, upper));
+
+            match _mm_movemask_epi8(found) {
+                0 => None,
+                mask => Some(offset + mask.trailing_zeros() as usize)
+            }
+        };
+
+        let last = haystack.len() - SSE2_WIDTH;
+        let mut offset = 0;
+
+        while offset < last {
+            if let Some(position) = find_in(offset) {
+                return Some(position);
+            }
+
+            offset += SSE2_WIDTH;
+        }
+
+        find_in(last)
+    }
+
+    // As `find_sse2`, for haystacks at least AVX2_WIDTH bytes long
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn find_avx2(needle: u8, haystack: &[u8]) -> Option<usize> {
+        let lower = _mm256_set1_epi8(needle.to_ascii_lowercase() as i8);
+        let upper = _mm256_set1_epi8(needle.to_ascii_uppercase() as i8);
+
+        let find_in = |offset: usize| {
+            let chunk = _mm256_loadu_si256(haystack.as_ptr().add(offset) as *const __m256i);
+            let found = _mm256_or_si256(_mm256_cmpeq_epi8(chunk, lower), _mm256_cmpeq_epi8(chunk, upper));
+
+            match _mm256_movemask_epi8(found) {
+                0 => None,
+                mask => Some(offset + mask.trailing_zeros() as usize)
+            }
+        };
+
+        let last = haystack.len() - AVX2_WIDTH;
+        let mut offset = 0;
+
+        while offset < last {
+            if let Some(position) = find_in(offset) {
+                return Some(position);
+            }
+
+            offset += AVX2_WIDTH;
+        }
+
+        find_in(last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use random::Random;
+
+    // Chars likely to trip up a bytewise matcher: mixed case, punctuation
+    // next to letters in ASCII, and non-ASCII chars, some of which lowercase
+    // to ASCII ones
+    const CHARS: &[char] = &['a', 'b', 'A', 'B', 'z', '/', '@', '`', '[', '{', 'é', 'É', '\u{212a}', 'k', '💣'];
+
+    // Half the strings are ASCII only
+    fn string(random: &mut Random, max_length: usize) -> String {
+        let chars = if random.below(2) == 0 { &CHARS[..10] } else { CHARS };
+        let length = random.below(max_length + 1);
+
+        (0..length).map(|_| random.pick(chars)).collect()
+    }
+
+    #[test]
+    fn test_find() {
+        let haystack = b"app/models/order_item/line_item.rb";
+
+        assert_eq!(find(b'a', haystack), Some(0));
+        assert_eq!(find(b'/', haystack), Some(3));
+        assert_eq!(find(b'B', haystack), Some(33));
+        assert_eq!(find(b'x', haystack), None);
+        assert_eq!(find(b'a', b""), None);
+    }
+
+    #[test]
+    fn test_find_random() {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+
+        for _ in 0..10_000 {
+            let haystack = string(&mut random, 80);
+            let needle = random.pick(&CHARS[..10]) as u8;
+            let expected = find_scalar(needle, haystack.as_bytes());
+
+            assert_eq!(find(needle, haystack.as_bytes()), expected, "{:?} in {:?}", needle as char, haystack);
+
+            #[cfg(target_arch = "x86_64")]
+            unsafe {
+                assert_eq!(x86::find_with_sse2(needle, haystack.as_bytes()), expected);
+
+                if haystack.len() >= x86::SSE2_WIDTH {
+                    assert_eq!(x86::find_sse2(needle, haystack.as_bytes()), expected);
+                }
+
+                if haystack.len() >= x86::AVX2_WIDTH && is_x86_feature_detected!("avx2") {
+                    assert_eq!(x86::find_avx2(needle, haystack.as_bytes()), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_matches_random() {
+        let mut random = Random(0x9e37_79b9_7f4a_7c15);
+
+        for _ in 0..10_000 {
+            let needle = string(&mut random, 4);
+            let haystack = string(&mut random, 80);
+
+            assert_eq!(
+                matches(&needle, &haystack),
+                matcher::matches(&needle, &haystack),
+                "{:?} in {:?}", needle, haystack
+            );
+        }
+    }
+}
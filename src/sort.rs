@@ -0,0 +1,213 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use rayon::prelude::*;
+
+use super::IndexedMatch;
+
+/// A criterion used to order matches with equal scores.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Tiebreak {
+    /// Prefer lines that appeared earlier in the input
+    Index,
+    /// Prefer shorter lines
+    Length,
+    /// Prefer matches that begin closer to the start of the line
+    Begin,
+    /// Prefer matches that end closer to the end of the line
+    End,
+    /// Prefer paths with fewer components
+    Depth,
+}
+
+impl FromStr for Tiebreak {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Tiebreak, String> {
+        match s {
+            "index" => Ok(Tiebreak::Index),
+            "length" => Ok(Tiebreak::Length),
+            "begin" => Ok(Tiebreak::Begin),
+            "end" => Ok(Tiebreak::End),
+            "depth" => Ok(Tiebreak::Depth),
+            _ => Err(format!("'{}' is not a valid tiebreak", s))
+        }
+    }
+}
+
+impl Tiebreak {
+    /// Whether this criterion needs the positions of the matched characters.
+    pub fn needs_positions(&self) -> bool {
+        matches!(*self, Tiebreak::Begin | Tiebreak::End)
+    }
+
+    fn compare(&self, a: &IndexedMatch, b: &IndexedMatch) -> Ordering {
+        match *self {
+            Tiebreak::Index => a.0.cmp(&b.0),
+            Tiebreak::Length => a.1.chars().count().cmp(&b.1.chars().count()),
+            Tiebreak::Begin => first(&a.3).cmp(&first(&b.3)),
+            Tiebreak::End => from_end(a).cmp(&from_end(b)),
+            Tiebreak::Depth => depth(a.1).cmp(&depth(b.1)),
+        }
+    }
+}
+
+fn first(positions: &[usize]) -> usize {
+    positions.first().cloned().unwrap_or(0)
+}
+
+fn from_end(&(_, line, _, ref positions): &IndexedMatch) -> usize {
+    match positions.last() {
+        Some(&last) => line.chars().count().saturating_sub(last + 1),
+        None => 0
+    }
+}
+
+fn depth(line: &str) -> usize {
+    line.trim_end_matches('/').matches('/').count()
+}
+
+/// Compares two scores such that higher scores sort first.
+///
+/// Unlike `partial_cmp`, this is a total order: NaN sorts after every other
+/// score, rather than panicking.
+#[inline]
+pub fn compare_scores(a: f64, b: f64) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => b.partial_cmp(&a).unwrap()
+    }
+}
+
+/// Compares two matches by score, falling back to each of `tiebreaks` in turn,
+/// and finally to their input index.
+///
+/// The result is deterministic regardless of the order `a` and `b` were
+/// produced in.
+pub fn compare(tiebreaks: &[Tiebreak], a: &IndexedMatch, b: &IndexedMatch) -> Ordering {
+    compare_scores(a.2, b.2).
+        then_with(|| {
+            tiebreaks.iter().
+                map(|tiebreak| tiebreak.compare(a, b)).
+                find(|&ordering| ordering != Ordering::Equal).
+                unwrap_or(Ordering::Equal)
+        }).
+        then_with(|| a.0.cmp(&b.0))
+}
+
+/// Sorts the best `limit` elements of `items` by `compare`, moving them to the
+/// front of the slice.
+///
+/// The remaining elements are left after them in no particular order, which
+/// makes this considerably cheaper than a full sort when only a handful of
+/// results are wanted from a large set.
+///
+/// # Examples
+///
+/// ```
+/// let mut items = vec![5, 1, 4, 2, 3];
+/// rff::sort::sort_top(&mut items, 2, |a, b| a.cmp(b));
+/// assert_eq!(&items[..2], &[1, 2]);
+/// ```
+pub fn sort_top<T, F>(items: &mut [T], limit: usize, compare: F)
+    where T: Send,
+          F: Fn(&T, &T) -> Ordering + Sync
+{
+    if limit == 0 {
+        return;
+    }
+
+    if limit < items.len() {
+        items.select_nth_unstable_by(limit - 1, &compare);
+    }
+
+    let top = if limit < items.len() { &mut items[..limit] } else { items };
+    top.par_sort_by(compare);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiebreak_from_str() {
+        assert_eq!("index".parse(), Ok(Tiebreak::Index));
+        assert_eq!("length".parse(), Ok(Tiebreak::Length));
+        assert_eq!("begin".parse(), Ok(Tiebreak::Begin));
+        assert_eq!("end".parse(), Ok(Tiebreak::End));
+        assert_eq!("depth".parse(), Ok(Tiebreak::Depth));
+        assert!("size".parse::<Tiebreak>().is_err());
+    }
+
+    #[test]
+    fn test_compare_scores() {
+        assert_eq!(compare_scores(1.0, 0.5), Ordering::Less);
+        assert_eq!(compare_scores(0.5, 1.0), Ordering::Greater);
+        assert_eq!(compare_scores(1.0, 1.0), Ordering::Equal);
+        assert_eq!(compare_scores(f64::INFINITY, 1.0), Ordering::Less);
+        assert_eq!(compare_scores(f64::NEG_INFINITY, f64::NAN), Ordering::Less);
+        assert_eq!(compare_scores(f64::NAN, f64::NEG_INFINITY), Ordering::Greater);
+        assert_eq!(compare_scores(f64::NAN, f64::NAN), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_compare() {
+        let a = (0, "a/b/cd", 1.0, vec![4, 5]);
+        let b = (1, "abc", 1.0, vec![1, 2]);
+        let c = (2, "xyz/abc", 2.0, vec![5, 6]);
+
+        assert_eq!(compare(&[], &c, &a), Ordering::Less);
+        assert_eq!(compare(&[], &a, &b), Ordering::Less);
+        assert_eq!(compare(&[Tiebreak::Length], &a, &b), Ordering::Greater);
+        assert_eq!(compare(&[Tiebreak::Begin], &a, &b), Ordering::Greater);
+        assert_eq!(compare(&[Tiebreak::End], &a, &b), Ordering::Less);
+        assert_eq!(compare(&[Tiebreak::Depth], &a, &b), Ordering::Greater);
+        assert_eq!(compare(&[Tiebreak::Depth, Tiebreak::Index], &b, &a), Ordering::Less);
+    }
+
+    #[test]
+    fn test_compare_is_total() {
+        let mut matches = [
+            (3, "d", f64::NAN, vec![]),
+            (1, "b", 1.0, vec![]),
+            (2, "c", 1.0, vec![]),
+            (0, "a", 2.0, vec![]),
+        ];
+
+        matches.sort_by(|a, b| compare(&[], a, b));
+
+        let order: Vec<_> = matches.iter().map(|m| m.0).collect();
+        assert_eq!(order, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_sort_top() {
+        let mut items = vec![9, 3, 7, 1, 8, 2, 6, 4, 5];
+        sort_top(&mut items, 3, |a, b| a.cmp(b));
+        assert_eq!(&items[..3], &[1, 2, 3]);
+
+        let mut rest = items[3..].to_vec();
+        rest.sort();
+        assert_eq!(rest, vec![4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_sort_top_limit_exceeds_length() {
+        let mut items = vec![3, 1, 2];
+        sort_top(&mut items, 10, |a, b| a.cmp(b));
+        assert_eq!(items, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_sort_top_zero() {
+        let mut items = vec![3, 1, 2];
+        sort_top(&mut items, 0, |a, b| a.cmp(b));
+        assert_eq!(items, vec![3, 1, 2]);
+
+        let mut empty: Vec<u32> = vec![];
+        sort_top(&mut empty, 5, |a, b| a.cmp(b));
+        assert!(empty.is_empty());
+    }
+}
@@ -1,34 +1,472 @@
-use std::io::{self, BufRead};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::iter::{self, FromIterator};
+use std::ops::Index;
+use std::path::Path;
+use std::str;
+use std::sync::{Arc, OnceLock};
 
-/// A useful alias for the backing storage we parse STDIN into.
-pub type InputLines = Vec<String>;
+use rayon::iter::Either;
+use rayon::prelude::*;
+
+use mmap::Mmap;
+
+// How many bytes of a mapped file each thread searches for line endings at
+// a time
+const SPLIT_CHUNK: usize = 1 << 20;
+
+// Roughly how many bytes of a mapped file's lines are split into lines at a
+// time, whenever they're first read. Lines begin less than this far into
+// their block, so their offsets within it fit in a u16.
+const BLOCK_SIZE: usize = 4 << 10;
+
+/// The backing storage we parse STDIN into: every line in one contiguous
+/// buffer, with the offsets at which each begins and ends. The lines of a
+/// mapped file are instead split only as they're read.
+#[derive(Clone, Debug)]
+pub struct InputLines {
+    storage: Storage,
+}
+
+#[derive(Clone, Debug)]
+enum Storage {
+    // lines read into memory without their line endings, line `i` spanning
+    // `offsets[i]..offsets[i + 1]`
+    Owned { buffer: String, offsets: Vec<usize> },
+    // a mapped file, with its line endings still in place
+    Mapped { map: Arc<Mmap>, blocks: Vec<Block> },
+}
+
+// A run of whole lines of a mapped file
+#[derive(Clone, Debug)]
+struct Block {
+    start: usize,
+    end: usize,
+    // the index of the block's first line, and how many lines it holds
+    first: usize,
+    len: usize,
+    // set if the lines weren't all valid UTF-8, and were decoded lossily
+    decoded: Option<Vec<String>>,
+    // where each line begins, relative to `start`, once any have been read
+    starts: OnceLock<Vec<u16>>,
+}
+
+impl Block {
+    // Takes `start..end` of `bytes` as a block, decoding its lines lossily
+    // if need be
+    fn new(bytes: &[u8], start: usize, end: usize) -> Block {
+        let lines = &bytes[start..end];
+
+        // line endings are ASCII, so every line is valid if the block is
+        let decoded = match str::from_utf8(lines) {
+            Ok(_) => None,
+            Err(_) => Some(split(lines).map(|line| String::from_utf8_lossy(line).into_owned()).collect())
+        };
+
+        // every line but the last ends in a newline
+        let newlines = lines.iter().filter(|&&b| b == b'\n').count();
+        let len = newlines + (lines.last() != Some(&b'\n')) as usize;
+
+        Block { start, end, first: 0, len, decoded, starts: OnceLock::new() }
+    }
+
+    // The line at `index` within the block, less its line ending
+    fn line<'a>(&'a self, map: &'a Mmap, index: usize) -> &'a str {
+        match self.decoded {
+            Some(ref lines) => &lines[index],
+            None => {
+                let bytes = &map.as_bytes()[self.start..self.end];
+                let starts = self.starts(bytes);
+                let end = starts.get(index + 1).map_or(bytes.len(), |&end| end as usize);
+
+                line(bytes, starts[index] as usize, end)
+            }
+        }
+    }
+
+    // The lines of the block, in order
+    fn lines<'a>(&'a self, map: &'a Mmap) -> impl Iterator<Item = &'a str> + 'a {
+        match self.decoded {
+            Some(ref lines) => Either::Left(lines.iter().map(String::as_str)),
+            None => {
+                let bytes = &map.as_bytes()[self.start..self.end];
+                let starts = self.starts(bytes);
+                let ends = starts[1..].iter().map(|&end| end as usize).chain(iter::once(bytes.len()));
+
+                Either::Right(starts.iter().zip(ends).map(move |(&start, end)| line(bytes, start as usize, end)))
+            }
+        }
+    }
+
+    // Where each of the block's lines begins within `bytes`, the block's
+    // contents, splitting them the first time they're read
+    fn starts(&self, bytes: &[u8]) -> &[u16] {
+        self.starts.get_or_init(|| {
+            let ends = bytes.iter().enumerate().filter(|&(_, &b)| b == b'\n').map(|(i, _)| i + 1);
+            iter::once(0).chain(ends).take(self.len).map(|start| start as u16).collect()
+        })
+    }
+}
+
+impl InputLines {
+    /// Creates an empty InputLines.
+    pub fn new() -> InputLines {
+        InputLines { storage: Storage::Owned { buffer: String::new(), offsets: vec![0] } }
+    }
+
+    /// Appends a line, which shouldn't include its line ending. The lines of
+    /// a mapped file are copied into memory first.
+    pub fn push(&mut self, line: &str) {
+        if let Storage::Mapped { .. } = self.storage {
+            let owned = self.iter().collect();
+            *self = owned;
+        }
+
+        if let Storage::Owned { ref mut buffer, ref mut offsets } = self.storage {
+            buffer.push_str(line);
+            offsets.push(buffer.len());
+        }
+    }
+
+    /// The number of lines.
+    pub fn len(&self) -> usize {
+        match self.storage {
+            Storage::Owned { ref offsets, .. } => offsets.len() - 1,
+            Storage::Mapped { ref blocks, .. } => blocks.last().map_or(0, |block| block.first + block.len)
+        }
+    }
+
+    /// Whether there are no lines.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The line at `index`, if there is one.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        if index < self.len() {
+            Some(&self[index])
+        } else {
+            None
+        }
+    }
+
+    /// Iterates over the lines in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rff::stdin::InputLines;
+    ///
+    /// let lines: InputLines = vec!["a", "b"].into_iter().collect();
+    /// assert_eq!(lines.iter().collect::<Vec<_>>(), vec!["a", "b"]);
+    /// ```
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = &'a str> + 'a {
+        match self.storage {
+            Storage::Owned { ref buffer, ref offsets } => {
+                Either::Left(offsets.windows(2).map(move |range| &buffer[range[0]..range[1]]))
+            },
+            Storage::Mapped { ref map, ref blocks } => Either::Right(blocks.iter().flat_map(move |block| block.lines(map)))
+        }
+    }
+
+    /// Iterates over the lines in parallel, see `iter`. Collecting them keeps
+    /// their order.
+    pub fn par_iter<'a>(&'a self) -> impl ParallelIterator<Item = &'a str> + 'a {
+        self.par_enumerate().map(|(_, line)| line)
+    }
+
+    /// Iterates over the lines in parallel along with their indices, as
+    /// `par_iter().enumerate()` would were the lines all split beforehand.
+    pub fn par_enumerate<'a>(&'a self) -> impl ParallelIterator<Item = (usize, &'a str)> + 'a {
+        match self.storage {
+            Storage::Owned { ref buffer, ref offsets } => {
+                Either::Left(offsets.par_windows(2).map(move |range| &buffer[range[0]..range[1]]).enumerate())
+            },
+            Storage::Mapped { ref map, ref blocks } => {
+                Either::Right(blocks.par_iter().flat_map_iter(move |block| (block.first..).zip(block.lines(map))))
+            }
+        }
+    }
+}
+
+impl Default for InputLines {
+    fn default() -> InputLines {
+        InputLines::new()
+    }
+}
+
+impl PartialEq for InputLines {
+    fn eq(&self, other: &InputLines) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl Index<usize> for InputLines {
+    type Output = str;
+
+    fn index(&self, index: usize) -> &str {
+        match self.storage {
+            Storage::Owned { ref buffer, ref offsets } => &buffer[offsets[index]..offsets[index + 1]],
+            Storage::Mapped { ref map, ref blocks } => {
+                assert!(index < self.len(), "line index out of bounds");
+
+                // the line is in the last block beginning at or before it
+                let block = &blocks[blocks.partition_point(|block| block.first <= index) - 1];
+                block.line(map, index - block.first)
+            }
+        }
+    }
+}
+
+impl<S: AsRef<str>> FromIterator<S> for InputLines {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> InputLines {
+        let mut lines = InputLines::new();
+
+        for line in iter {
+            lines.push(line.as_ref());
+        }
+
+        lines
+    }
+}
 
 /// Pulls lines of input from STDIN into an `InputLines`.
 pub fn slurp() -> InputLines {
     let stdin = io::stdin();
     let stdin = stdin.lock();
-    read_lines(stdin)
+    read_lines(stdin).unwrap()
 }
 
-fn read_lines<T: BufRead>(buf: T) -> InputLines {
-    buf.lines().map(Result::unwrap).collect()
+/// Maps the file at `path` into memory, splitting it into lines only as
+/// they're read. Files that can't be mapped, like pipes, are read instead,
+/// as are those reporting no size, like many in /proc. Lines that aren't
+/// valid UTF-8 are decoded lossily.
+///
+/// # Safety
+///
+/// The file mustn't be modified or truncated while its lines are in use,
+/// see `Mmap::map`.
+pub unsafe fn map_file(path: &Path) -> io::Result<InputLines> {
+    let file = File::open(path)?;
+    let metadata = file.metadata()?;
+
+    if !metadata.is_file() || metadata.len() == 0 {
+        return read_lines(BufReader::new(file));
+    }
+
+    let map = Mmap::map(&file)?;
+    let blocks = split_blocks(map.as_bytes());
+
+    Ok(InputLines { storage: Storage::Mapped { map: Arc::new(map), blocks } })
+}
+
+// Divides a mapped file into blocks of lines. The first begins the file, and
+// each other at the first line beginning at or after a multiple of
+// BLOCK_SIZE, so a block only spans more than that if a line does.
+fn split_blocks(bytes: &[u8]) -> Vec<Block> {
+    let starts: Vec<Vec<usize>> = bytes.par_chunks(SPLIT_CHUNK).
+        enumerate().
+        map(|(chunk, chunk_bytes)| {
+            let offset = chunk * SPLIT_CHUNK;
+            let mut next = offset.max(BLOCK_SIZE);
+            let mut starts = vec![];
+
+            for (i, _) in chunk_bytes.iter().enumerate().filter(|&(_, &b)| b == b'\n') {
+                let start = offset + i + 1;
+
+                if start >= next && start < bytes.len() {
+                    starts.push(start);
+                    next = (start / BLOCK_SIZE + 1) * BLOCK_SIZE;
+                }
+            }
+
+            starts
+        }).
+        collect();
+
+    let bounds: Vec<usize> = iter::once(0).
+        chain(starts.into_iter().flatten()).
+        chain(iter::once(bytes.len())).
+        collect();
+
+    let mut blocks: Vec<Block> = bounds.par_windows(2).
+        map(|bounds| Block::new(bytes, bounds[0], bounds[1])).
+        collect();
+
+    let mut first = 0;
+
+    for block in &mut blocks {
+        block.first = first;
+        first += block.len;
+    }
+
+    blocks
+}
+
+// Splits `bytes` into lines, dropping their line endings ("\n" or "\r\n")
+fn split<'a>(bytes: &'a [u8]) -> impl Iterator<Item = &'a [u8]> + 'a {
+    bytes.split_inclusive(|&b| b == b'\n').map(strip_ending)
+}
+
+// The line spanning `start..end` of a valid UTF-8 block, less its line ending
+fn line(bytes: &[u8], start: usize, end: usize) -> &str {
+    let line = strip_ending(&bytes[start..end]);
+
+    // the block was valid UTF-8 when mapped, and stays so as long as the file
+    // isn't modified, see `map_file`
+    unsafe { str::from_utf8_unchecked(line) }
+}
+
+fn strip_ending(line: &[u8]) -> &[u8] {
+    match line.strip_suffix(b"\n") {
+        Some(line) => line.strip_suffix(b"\r").unwrap_or(line),
+        None => line
+    }
+}
+
+// Reads all of `buf`, then drops the line endings in place, leaving only the
+// lines themselves in the buffer. Input that isn't valid UTF-8 is decoded
+// lossily, a line at a time.
+fn read_lines<T: BufRead>(mut buf: T) -> io::Result<InputLines> {
+    let mut bytes = vec![];
+    buf.read_to_end(&mut bytes)?;
+
+    if str::from_utf8(&bytes).is_err() {
+        return Ok(split(&bytes).map(String::from_utf8_lossy).collect());
+    }
+
+    let mut offsets = vec![0];
+    let mut start = 0;
+    let mut end = 0;
+
+    while start < bytes.len() {
+        let (mut line_end, next) = match bytes[start..].iter().position(|&b| b == b'\n') {
+            Some(position) => (start + position, start + position + 1),
+            None => (bytes.len(), bytes.len())
+        };
+
+        if next > line_end && line_end > start && bytes[line_end - 1] == b'\r' {
+            line_end -= 1;
+        }
+
+        bytes.copy_within(start..line_end, end);
+        end += line_end - start;
+        offsets.push(end);
+        start = next;
+    }
+
+    bytes.truncate(end);
+
+    // line endings are ASCII, so dropping them from valid input leaves every
+    // line valid
+    let buffer = unsafe { String::from_utf8_unchecked(bytes) };
+
+    Ok(InputLines { storage: Storage::Owned { buffer, offsets } })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::{env, fs, process};
 
     #[test]
     fn test_read_lines() {
         let input = b"a\nb\nc";
         let slice = &input[..];
 
-        let expected = [
-            String::from("a"),
-            String::from("b"),
-            String::from("c")
-        ];
+        let expected: InputLines = vec!["a", "b", "c"].into_iter().collect();
+
+        assert_eq!(read_lines(slice).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_read_lines_endings() {
+        let lines = read_lines(&b"a\r\n\nb\rc\n"[..]).unwrap();
+        assert_eq!(lines.iter().collect::<Vec<_>>(), vec!["a", "", "b\rc"]);
+
+        assert!(read_lines(&b""[..]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_lines_invalid() {
+        // the halves of "é" on separate lines are each invalid
+        let lines = read_lines(&b"a\n\xff\r\n\xc3\n\xa9"[..]).unwrap();
+        assert_eq!(lines.iter().collect::<Vec<_>>(), vec!["a", "\u{fffd}", "\u{fffd}", "\u{fffd}"]);
+    }
+
+    #[test]
+    fn test_map_file() {
+        let path = env::temp_dir().join(format!("rff-map-file-{}", process::id()));
+
+        fs::write(&path, "a\r\n\nb\rc\nd").unwrap();
+        let lines = unsafe { map_file(&path).unwrap() };
+        assert_eq!(lines.iter().collect::<Vec<_>>(), vec!["a", "", "b\rc", "d"]);
+
+        // pushing copies the lines out of the map, leaving clones mapped
+        let mut pushed = lines.clone();
+        pushed.push("e");
+        assert_eq!(pushed.iter().collect::<Vec<_>>(), vec!["a", "", "b\rc", "d", "e"]);
+        assert_eq!(lines.len(), 4);
+        drop(lines);
+
+        fs::write(&path, "a\n").unwrap();
+        assert_eq!(unsafe { map_file(&path).unwrap() }.iter().collect::<Vec<_>>(), vec!["a"]);
+
+        fs::write(&path, "").unwrap();
+        assert!(unsafe { map_file(&path).unwrap() }.is_empty());
+
+        fs::write(&path, b"a\n\xff\nb").unwrap();
+        assert_eq!(unsafe { map_file(&path).unwrap() }.iter().collect::<Vec<_>>(), vec!["a", "\u{fffd}", "b"]);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_map_file_blocks() {
+        let path = env::temp_dir().join(format!("rff-map-file-blocks-{}", process::id()));
+
+        // lines spanning blocks and chunks, and blocks with invalid lines
+        let mut expected: Vec<String> = (0..20_000).map(|i| format!("{}{}", "x".repeat(i % 300), i)).collect();
+        expected[5_000] = "y".repeat(SPLIT_CHUNK + BLOCK_SIZE);
+        expected[5_001] = String::new();
+
+        let mut contents = expected.join("\n").into_bytes();
+        contents.extend(b"\r\n\xff");
+        expected.push(String::from("\u{fffd}"));
+
+        fs::write(&path, &contents).unwrap();
+        let lines = unsafe { map_file(&path).unwrap() };
+
+        assert_eq!(lines.len(), expected.len());
+        assert!(lines.iter().eq(expected.iter().map(String::as_str)));
+        assert_eq!(lines.par_iter().collect::<Vec<_>>(), expected);
+        assert!(lines.par_enumerate().all(|(index, line)| line == expected[index]));
+
+        for &index in &[0, 1, 4_999, 5_000, 5_001, 5_002, 12_345, 20_000] {
+            assert_eq!(&lines[index], expected[index]);
+        }
+
+        assert_eq!(lines.get(20_001), None);
+
+        drop(lines);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_map_file_without_size() {
+        // procfs reports a size of 0, but has contents when read
+        assert!(!unsafe { map_file(Path::new("/proc/self/mounts")) }.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_index() {
+        let lines: InputLines = vec!["app", "", "models"].into_iter().collect();
 
-        assert_eq!(read_lines(slice), expected);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(&lines[0], "app");
+        assert_eq!(&lines[1], "");
+        assert_eq!(lines.get(2), Some("models"));
+        assert_eq!(lines.get(3), None);
     }
 }
@@ -1,19 +1,28 @@
-use std::io::{Read, Result};
+use std::io::{Chain, Cursor, Read, Result};
 
 use super::event::{self, Event, Key};
 
 pub struct Events<R> {
-    source: R,
+    // input already read from elsewhere, followed by the source itself
+    source: Chain<Cursor<Vec<u8>>, R>,
     leftover: Option<u8>
 }
 
 impl<R: Read> Events<R> {
-    pub fn new(source: R) -> Events<R> {
+    /// Creates Events that parse `typeahead` before reading from `source`.
+    pub fn with_typeahead(typeahead: Vec<u8>, source: R) -> Events<R> {
         Events {
-            source: source,
+            source: Cursor::new(typeahead).chain(source),
             leftover: None
         }
     }
+
+    /// Whether input has already been read from `source` that will be parsed
+    /// by the next call to `next`.
+    pub fn pending(&self) -> bool {
+        let (typeahead, _) = self.source.get_ref();
+        self.leftover.is_some() || (typeahead.position() as usize) < typeahead.get_ref().len()
+    }
 }
 
 impl<R: Read> Iterator for Events<R> {
@@ -4,13 +4,18 @@ use std::mem;
 use std::fs::{File, OpenOptions};
 use std::io::{self, Write, Read};
 use std::os::unix::io::AsRawFd;
+use std::time::Duration;
 use libc::{TCSANOW, TIOCGWINSZ, winsize};
 use libc::{termios, tcgetattr, tcsetattr};
 use libc::{ioctl, cfmakeraw};
+use libc::{poll, pollfd, POLLIN};
 
 pub use self::input::*;
 pub use self::event::*;
 
+// How long to wait for the terminal to report the cursor position, in ms
+const REPORT_TIMEOUT: u64 = 500;
+
 #[derive(Debug)]
 pub enum Error {
     TcGetAttr,
@@ -20,6 +25,8 @@ pub enum Error {
 pub struct Terminal {
     file: File,
     prev_termios: Option<termios>,
+    // keys read while waiting for a cursor position report, for `events`
+    typeahead: Vec<u8>,
     pub max_width: usize,
     pub max_height: usize
 }
@@ -33,6 +40,7 @@ impl Terminal {
         let mut terminal = Terminal {
             file: file,
             prev_termios: None,
+            typeahead: vec![],
             max_width: 80,
             max_height: 25
         };
@@ -88,8 +96,106 @@ impl Terminal {
         Ok(())
     }
 
-    pub fn events(&self) -> io::Result<Events<File>> {
-        self.file.try_clone().map(Events::new)
+    /// Queries the terminal for the cursor's (row, column) position, which
+    /// is 1-based. Fails if the terminal doesn't report it promptly, as not
+    /// every terminal supports the query.
+    ///
+    /// Raw mode must be enabled, or the report would be echoed.
+    pub fn cursor_position(&mut self) -> io::Result<(usize, usize)> {
+        write!(self.file, "\x1b[6n")?;
+        self.file.flush()?;
+
+        let timeout = Duration::from_millis(REPORT_TIMEOUT);
+        let mut report = vec![];
+        let mut byte = [0u8; 1];
+
+        loop {
+            let read = self.wait_for_input(Some(timeout)).and_then(|ready| {
+                if ready { self.file.read(&mut byte) } else { Ok(0) }
+            });
+
+            match read {
+                Ok(1) if scan_report(&mut report, &mut self.typeahead, byte[0]) => break,
+                Ok(1) => continue,
+                Ok(_) => {
+                    self.typeahead.append(&mut report);
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "no cursor position report"));
+                },
+                Err(err) => {
+                    self.typeahead.append(&mut report);
+                    return Err(err);
+                }
+            }
+        }
+
+        parse_cursor_position(&report).
+            ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid cursor position report"))
+    }
+
+    /// Waits up to `timeout`, or indefinitely if `None`, for input to read,
+    /// returning whether there is any.
+    pub fn wait_for_input(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        let timeout = timeout.map_or(-1, |timeout| timeout.as_millis() as i32);
+        let mut fds = pollfd { fd: self.file.as_raw_fd(), events: POLLIN, revents: 0 };
+
+        match unsafe { poll(&mut fds, 1, timeout) } {
+            -1 => {
+                let err = io::Error::last_os_error();
+
+                // a signal (e.g. a resize) arriving isn't a failure
+                if err.kind() == io::ErrorKind::Interrupted { Ok(false) } else { Err(err) }
+            },
+            0 => Ok(false),
+            _ => Ok(true)
+        }
+    }
+
+    /// Reads events from the terminal, starting with any keys typed while
+    /// waiting for `cursor_position`.
+    pub fn events(&mut self) -> io::Result<Events<File>> {
+        let typeahead = mem::take(&mut self.typeahead);
+        self.file.try_clone().map(|file| Events::with_typeahead(typeahead, file))
+    }
+}
+
+// Adds `byte` to the cursor position `report` being read, returning whether
+// the report is complete. Bytes that can't be part of a report, like keys
+// typed before it arrived, are moved to `typeahead` instead.
+fn scan_report(report: &mut Vec<u8>, typeahead: &mut Vec<u8>, byte: u8) -> bool {
+    report.push(byte);
+
+    // `ESC [`, then the row and column separated by `;`, then `R`
+    let valid = match (report.len(), byte) {
+        (1, b'\x1b') => true,
+        (2, b'[') => true,
+        (n, b'0'..=b'9') => n > 2,
+        (n, b';') => n > 3 && !report[..n - 1].contains(&b';') && report[n - 2] != b'[',
+        (n, b'R') => n > 4 && report[..n - 1].contains(&b';') && report[n - 2] != b';',
+        _ => false
+    };
+
+    if !valid {
+        let restart = byte == b'\x1b';
+        let end = if restart { report.len() - 1 } else { report.len() };
+
+        typeahead.extend(report.drain(..end));
+        return false;
+    }
+
+    byte == b'R'
+}
+
+// Parses a cursor position report, of the form `ESC [ row ; column R`
+fn parse_cursor_position(report: &[u8]) -> Option<(usize, usize)> {
+    let start = report.iter().rposition(|&b| b == b'\x1b')?;
+    let report = std::str::from_utf8(&report[start..]).ok()?;
+    let report = report.strip_prefix("\x1b[")?.strip_suffix('R')?;
+
+    let mut position = report.splitn(2, ';').map(str::parse);
+
+    match (position.next(), position.next()) {
+        (Some(Ok(row)), Some(Ok(column))) => Some((row, column)),
+        _ => None
     }
 }
 
@@ -120,6 +226,31 @@ mod tests {
     use super::*;
     use std::io::Write;
 
+    // Scans `input` for a cursor position report, returning the report
+    // and the keys typed around it
+    fn scan(input: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut report = vec![];
+        let mut typeahead = vec![];
+
+        for &byte in input {
+            if scan_report(&mut report, &mut typeahead, byte) {
+                break;
+            }
+        }
+
+        (report, typeahead)
+    }
+
+    #[test]
+    fn test_scan_report() {
+        assert_eq!(scan(b"\x1b[12;40R"), (b"\x1b[12;40R".to_vec(), vec![]));
+        assert_eq!(scan(b"READ\x1b[1;1R"), (b"\x1b[1;1R".to_vec(), b"READ".to_vec()));
+
+        // other escape sequences are keys, even those starting `ESC [`
+        assert_eq!(scan(b"\x1b[A\x1b\x1b[2;3R"), (b"\x1b[2;3R".to_vec(), b"\x1b[A\x1b".to_vec()));
+        assert_eq!(scan(b"\x1b[;1R\x1b[1;R"), (vec![], b"\x1b[;1R\x1b[1;R".to_vec()));
+    }
+
     #[test]
     fn terminal() {
         let mut term = Terminal::from("/dev/tty").expect("Unable to open /dev/tty");
@@ -131,4 +262,13 @@ mod tests {
         write!(term, "").unwrap();
         term.flush().unwrap();
     }
+
+    #[test]
+    fn test_parse_cursor_position() {
+        assert_eq!(parse_cursor_position(b"\x1b[12;1R"), Some((12, 1)));
+        assert_eq!(parse_cursor_position(b"ab\x1b[3;40R"), Some((3, 40)));
+        assert_eq!(parse_cursor_position(b"\x1b[12R"), None);
+        assert_eq!(parse_cursor_position(b"\x1b[12;1"), None);
+        assert_eq!(parse_cursor_position(b""), None);
+    }
 }
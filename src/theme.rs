@@ -0,0 +1,419 @@
+//! Colors used to draw each element of the interface.
+//!
+//! A theme starts from one of the built-in presets (`dark`, `light` or `mono`)
+//! and can be customised with a comma-separated list of `element:style`
+//! pairs, e.g. `light,highlight:#ff8700,selected:white/blue`.
+//!
+//! A style is a foreground color, optionally followed by `/` and a background
+//! color, or one of `bold`, `underline` and `reverse` (which swaps the line's
+//! foreground and background). Colors are one of the 16 base color names
+//! (`red`, `light-red`, ...), a 256-color index (`0` to `255`), a truecolor
+//! hex code (`#rrggbb`), or `default`. Colors the terminal can't display are
+//! replaced by the nearest that it can (see `Theme::downgrade`).
+
+use std::env;
+use std::ffi::OsString;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use ansi::color::{self, AnsiValue, Colors, Depth, Rgb};
+
+/// A color from the 16 base colors, the 256-color palette, or truecolor.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Color {
+    /// The terminal's default color
+    Default,
+    Base(Colors),
+    Indexed(AnsiValue),
+    Rgb(Rgb),
+}
+
+impl Color {
+    /// The nearest color a terminal of `depth` can display.
+    pub fn downgrade(self, depth: Depth) -> Color {
+        match (self, depth) {
+            (Color::Rgb(rgb), Depth::Indexed) => Color::Indexed(rgb.to_ansi_value()),
+            (Color::Rgb(rgb), Depth::Base) => Color::Base(rgb.to_base()),
+            (Color::Indexed(value), Depth::Base) => Color::Base(value.to_base()),
+            (color, _) => color
+        }
+    }
+}
+
+const BASE_COLORS: [(&str, Colors); 16] = [
+    ("black", Colors::Black),
+    ("red", Colors::Red),
+    ("green", Colors::Green),
+    ("yellow", Colors::Yellow),
+    ("blue", Colors::Blue),
+    ("magenta", Colors::Magenta),
+    ("cyan", Colors::Cyan),
+    ("white", Colors::White),
+    ("light-black", Colors::LightBlack),
+    ("light-red", Colors::LightRed),
+    ("light-green", Colors::LightGreen),
+    ("light-yellow", Colors::LightYellow),
+    ("light-blue", Colors::LightBlue),
+    ("light-magenta", Colors::LightMagenta),
+    ("light-cyan", Colors::LightCyan),
+    ("light-white", Colors::LightWhite),
+];
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Color, String> {
+        if s == "default" {
+            return Ok(Color::Default);
+        }
+
+        if let Some(&(_, color)) = BASE_COLORS.iter().find(|&&(name, _)| name == s) {
+            return Ok(Color::Base(color));
+        }
+
+        if let Ok(index) = s.parse() {
+            return Ok(Color::Indexed(AnsiValue(index)));
+        }
+
+        if s.len() == 7 && s.starts_with('#') && s[1..].chars().all(|c| c.is_ascii_hexdigit()) {
+            let channel = |i: usize| u8::from_str_radix(&s[i..i + 2], 16).unwrap();
+            return Ok(Color::Rgb(Rgb(channel(1), channel(3), channel(5))));
+        }
+
+        Err(format!("'{}' is not a valid color", s))
+    }
+}
+
+impl color::Color for Color {
+    fn write_fg(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Color::Default => color::Reset.write_fg(f),
+            Color::Base(color) => color.write_fg(f),
+            Color::Indexed(value) => value.write_fg(f),
+            Color::Rgb(rgb) => rgb.write_fg(f),
+        }
+    }
+
+    fn write_bg(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Color::Default => color::Reset.write_bg(f),
+            Color::Base(color) => color.write_bg(f),
+            Color::Indexed(value) => value.write_bg(f),
+            Color::Rgb(rgb) => rgb.write_bg(f),
+        }
+    }
+}
+
+/// How a single element of the interface is drawn.
+///
+/// Displaying a `Style` writes the sequences that apply it, on top of
+/// whatever style is already in effect.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    /// Whether the foreground and background colors are swapped
+    pub invert: bool,
+    pub bold: bool,
+    pub underline: bool,
+}
+
+impl Style {
+    fn fg(color: Colors) -> Style {
+        Style { fg: Some(Color::Base(color)), ..Style::default() }
+    }
+
+    fn invert() -> Style {
+        Style { invert: true, ..Style::default() }
+    }
+
+    fn bold() -> Style {
+        Style { bold: true, ..Style::default() }
+    }
+
+    fn underline() -> Style {
+        Style { underline: true, ..Style::default() }
+    }
+
+    /// This style with its colors downgraded to `depth`.
+    pub fn downgrade(self, depth: Depth) -> Style {
+        Style {
+            fg: self.fg.map(|color| color.downgrade(depth)),
+            bg: self.bg.map(|color| color.downgrade(depth)),
+            ..self
+        }
+    }
+
+    /// Whether displaying this style writes nothing.
+    pub fn is_plain(&self) -> bool {
+        *self == Style::default()
+    }
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Style, String> {
+        match s {
+            "reverse" => return Ok(Style::invert()),
+            "bold" => return Ok(Style::bold()),
+            "underline" => return Ok(Style::underline()),
+            _ => {}
+        }
+
+        let mut colors = s.splitn(2, '/');
+        let fg = colors.next().unwrap_or("");
+        let bg = colors.next().unwrap_or("");
+
+        let parse = |color: &str| {
+            if color.is_empty() { Ok(None) } else { color.parse().map(Some) }
+        };
+
+        Ok(Style { fg: parse(fg)?, bg: parse(bg)?, ..Style::default() })
+    }
+}
+
+impl Display for Style {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.invert {
+            write!(f, "\x1b[7m")?;
+        }
+
+        if self.bold {
+            write!(f, "\x1b[1m")?;
+        }
+
+        if self.underline {
+            write!(f, "\x1b[4m")?;
+        }
+
+        if let Some(fg) = self.fg {
+            write!(f, "{}", color::Fg(fg))?;
+        }
+
+        if let Some(bg) = self.bg {
+            write!(f, "{}", color::Bg(bg))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The styles of each element of the interface.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Theme {
+    /// The `>` separating the match count from the search term
+    pub prompt: Style,
+    /// The number of matches
+    pub counter: Style,
+    /// Matched chars
+    pub highlight: Style,
+    /// The selected line
+    pub selected: Style,
+    /// Matched chars on the selected line, drawn on top of `selected`
+    pub selected_highlight: Style,
+    /// The markers standing in for text scrolled or truncated out of view
+    pub marker: Style,
+    /// Messages such as an invalid regex
+    pub error: Style,
+}
+
+impl Theme {
+    /// A theme for terminals with a dark background.
+    pub fn dark() -> Theme {
+        Theme {
+            prompt: Style::default(),
+            counter: Style::default(),
+            highlight: Style::fg(Colors::Magenta),
+            selected: Style::invert(),
+            selected_highlight: Style::fg(Colors::Magenta),
+            marker: Style::fg(Colors::LightBlack),
+            error: Style::fg(Colors::Red),
+        }
+    }
+
+    /// A theme for terminals with a light background.
+    pub fn light() -> Theme {
+        Theme {
+            prompt: Style::fg(Colors::Blue),
+            counter: Style::fg(Colors::LightBlack),
+            highlight: Style::fg(Colors::Blue),
+            selected: Style { bg: Some(Color::Indexed(AnsiValue(254))), ..Style::default() },
+            selected_highlight: Style::fg(Colors::Magenta),
+            marker: Style::fg(Colors::LightBlack),
+            error: Style::fg(Colors::Red),
+        }
+    }
+
+    /// A theme using no colors at all, for limited terminals or when colors
+    /// are unwanted.
+    pub fn mono() -> Theme {
+        Theme {
+            prompt: Style::default(),
+            counter: Style::default(),
+            highlight: Style::underline(),
+            selected: Style::invert(),
+            selected_highlight: Style { bold: true, underline: true, ..Style::default() },
+            marker: Style::default(),
+            error: Style::bold(),
+        }
+    }
+
+    /// This theme with all its colors downgraded to `depth`.
+    pub fn downgrade(self, depth: Depth) -> Theme {
+        Theme {
+            prompt: self.prompt.downgrade(depth),
+            counter: self.counter.downgrade(depth),
+            highlight: self.highlight.downgrade(depth),
+            selected: self.selected.downgrade(depth),
+            selected_highlight: self.selected_highlight.downgrade(depth),
+            marker: self.marker.downgrade(depth),
+            error: self.error.downgrade(depth),
+        }
+    }
+
+    // Sets the style of the element called `name`
+    fn set(&mut self, name: &str, style: Style) -> Result<(), String> {
+        let element = match name {
+            "prompt" => &mut self.prompt,
+            "counter" => &mut self.counter,
+            "highlight" => &mut self.highlight,
+            "selected" => &mut self.selected,
+            "selected-highlight" => &mut self.selected_highlight,
+            "marker" => &mut self.marker,
+            "error" => &mut self.error,
+            _ => return Err(format!("'{}' is not a valid element", name))
+        };
+
+        *element = style;
+        Ok(())
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::dark()
+    }
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    /// Parses a `--color` specification, applied on top of the dark preset.
+    fn from_str(s: &str) -> Result<Theme, String> {
+        let mut theme = Theme::default();
+
+        for item in s.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            match item.find(':') {
+                Some(i) => theme.set(&item[..i], item[i + 1..].parse()?)?,
+                None => theme = match item {
+                    "dark" => Theme::dark(),
+                    "light" => Theme::light(),
+                    "mono" => Theme::mono(),
+                    _ => return Err(format!("'{}' is not a valid theme", item))
+                }
+            }
+        }
+
+        Ok(theme)
+    }
+}
+
+/// Whether colors have been disabled through the environment, by setting
+/// `NO_COLOR` (see https://no-color.org) or using a dumb terminal.
+pub fn colors_disabled() -> bool {
+    colors_disabled_by(env::var_os("NO_COLOR"), env::var_os("TERM"))
+}
+
+fn colors_disabled_by(no_color: Option<OsString>, term: Option<OsString>) -> bool {
+    let no_color = no_color.is_some_and(|value| !value.is_empty());
+    no_color || term.is_some_and(|term| term == "dumb")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_from_str() {
+        assert_eq!("default".parse(), Ok(Color::Default));
+        assert_eq!("red".parse(), Ok(Color::Base(Colors::Red)));
+        assert_eq!("light-cyan".parse(), Ok(Color::Base(Colors::LightCyan)));
+        assert_eq!("208".parse(), Ok(Color::Indexed(AnsiValue(208))));
+        assert_eq!("#ff8700".parse(), Ok(Color::Rgb(Rgb(255, 135, 0))));
+        assert!("256".parse::<Color>().is_err());
+        assert!("#ff87".parse::<Color>().is_err());
+        assert!("#gg8700".parse::<Color>().is_err());
+        assert!("purple".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn test_style_from_str() {
+        let style: Style = "white/#000080".parse().unwrap();
+        assert_eq!(style.fg, Some(Color::Base(Colors::White)));
+        assert_eq!(style.bg, Some(Color::Rgb(Rgb(0, 0, 128))));
+
+        let style: Style = "/blue".parse().unwrap();
+        assert_eq!(style.fg, None);
+        assert_eq!(style.bg, Some(Color::Base(Colors::Blue)));
+
+        assert!("reverse".parse::<Style>().unwrap().invert);
+        assert!("bold".parse::<Style>().unwrap().bold);
+        assert!("underline".parse::<Style>().unwrap().underline);
+        assert!("red/purple".parse::<Style>().is_err());
+    }
+
+    #[test]
+    fn test_style_display() {
+        assert_eq!(format!("{}", Style::default()), "");
+        assert_eq!(format!("{}", Style::invert()), "\x1b[7m");
+        assert_eq!(format!("{}", Theme::mono().selected_highlight), "\x1b[1m\x1b[4m");
+
+        let style: Style = "208/#102030".parse().unwrap();
+        assert_eq!(format!("{}", style), "\x1b[38;5;208m\x1b[48;2;16;32;48m");
+
+        let style: Style = "default".parse().unwrap();
+        assert_eq!(format!("{}", style), "\x1b[39m");
+    }
+
+    #[test]
+    fn test_downgrade() {
+        let orange = Color::Rgb(Rgb(255, 135, 0));
+        assert_eq!(orange.downgrade(Depth::TrueColor), orange);
+        assert_eq!(orange.downgrade(Depth::Indexed), Color::Indexed(AnsiValue(208)));
+        assert_eq!(orange.downgrade(Depth::Base), Color::Base(Colors::Yellow));
+        assert_eq!(Color::Indexed(AnsiValue(254)).downgrade(Depth::Base), Color::Base(Colors::White));
+        assert_eq!(Color::Default.downgrade(Depth::Base), Color::Default);
+
+        let theme = Theme::light().downgrade(Depth::Base);
+        assert_eq!(theme.selected.bg, Some(Color::Base(Colors::White)));
+    }
+
+    #[test]
+    fn test_theme_from_str() {
+        assert_eq!("".parse(), Ok(Theme::dark()));
+        assert_eq!("light".parse(), Ok(Theme::light()));
+
+        let theme: Theme = "light,highlight:red, selected-highlight:#ff0000".parse().unwrap();
+        assert_eq!(theme.highlight, Style::fg(Colors::Red));
+        assert_eq!(theme.selected_highlight.fg, Some(Color::Rgb(Rgb(255, 0, 0))));
+        assert_eq!(theme.prompt, Theme::light().prompt);
+
+        assert!("solarized".parse::<Theme>().is_err());
+        assert!("pointer:red".parse::<Theme>().is_err());
+        assert!("header:red".parse::<Theme>().is_err());
+        assert!("border:red".parse::<Theme>().is_err());
+        assert!("highlight:purple".parse::<Theme>().is_err());
+    }
+
+    #[test]
+    fn test_colors_disabled() {
+        let var = |value: &str| Some(OsString::from(value));
+
+        assert!(!colors_disabled_by(None, var("xterm-256color")));
+        assert!(!colors_disabled_by(None, None));
+        assert!(!colors_disabled_by(var(""), var("xterm")));
+        assert!(colors_disabled_by(var("1"), var("xterm")));
+        assert!(colors_disabled_by(None, var("dumb")));
+    }
+}